@@ -0,0 +1,79 @@
+//! Tail latency against file size. `-n` only reads back as far as the lines it needs, so the
+//! time to print the last lines should stay flat as the file grows.
+//!
+//! Run with `cargo bench --bench tail`.
+
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+const SIZES_MB: [u64; 3] = [1, 64, 512];
+const LINES: [&str; 2] = ["10", "100000"];
+const RUNS: usize = 5;
+
+fn fixture_path(size_mb: u64) -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("tail-{}mb.log", size_mb))
+}
+
+// Lines shaped like a typical access log, generated once per size.
+fn fixture(size_mb: u64) -> PathBuf {
+    let path = fixture_path(size_mb);
+    if path.metadata().is_ok_and(|m| m.len() >= size_mb << 20) {
+        return path;
+    }
+
+    let mut out = BufWriter::new(File::create(&path).unwrap());
+    let mut written = 0;
+    let mut i = 0u64;
+    while written < size_mb << 20 {
+        let line = format!(
+            "2026-10-18T12:00:00.{:06}Z INFO request id={} path=/api/v1/items status=200\n",
+            i % 1_000_000,
+            i
+        );
+        out.write_all(line.as_bytes()).unwrap();
+        written += line.len() as u64;
+        i += 1;
+    }
+    out.flush().unwrap();
+    path
+}
+
+fn median(mut runs: Vec<Duration>) -> Duration {
+    runs.sort();
+    runs[runs.len() / 2]
+}
+
+fn main() {
+    println!("{:>8} {:>8} {:>12}", "size", "-n", "median");
+
+    for size_mb in SIZES_MB {
+        let path = fixture(size_mb);
+
+        for lines in LINES {
+            let runs = (0..RUNS)
+                .map(|_| {
+                    let started = Instant::now();
+                    let status = Command::new(env!("CARGO_BIN_EXE_trunk"))
+                        .args(["-n", lines, path.to_str().unwrap()])
+                        .stdin(Stdio::null())
+                        .stdout(Stdio::null())
+                        .status()
+                        .unwrap();
+                    assert!(status.success());
+                    started.elapsed()
+                })
+                .collect();
+
+            println!("{:>6}MB {:>8} {:>12?}", size_mb, lines, median(runs));
+        }
+    }
+
+    for size_mb in SIZES_MB {
+        let _ = fs::remove_file(fixture_path(size_mb));
+    }
+}
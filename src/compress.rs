@@ -0,0 +1,86 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+// The bytes each format starts with.
+const GZIP: &[u8] = &[0x1f, 0x8b];
+const ZSTD: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const XZ: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const BZIP2: &[u8] = b"BZh";
+
+// Extensions a rotated, compressed generation of a log can have.
+const EXTENSIONS: [&str; 4] = ["gz", "zst", "xz", "bz2"];
+
+/// How a file is compressed, told by its first bytes rather than its name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+impl Compression {
+    /// The compression of a file, or None if it isn't compressed.
+    pub fn detect(f: &mut File) -> io::Result<Option<Self>> {
+        let mut magic = Vec::with_capacity(XZ.len());
+        f.by_ref().take(XZ.len() as u64).read_to_end(&mut magic)?;
+        f.seek(SeekFrom::Start(0))?;
+
+        Ok([
+            (GZIP, Compression::Gzip),
+            (ZSTD, Compression::Zstd),
+            (XZ, Compression::Xz),
+            (BZIP2, Compression::Bzip2),
+        ]
+        .into_iter()
+        .find(|(prefix, _)| magic.starts_with(prefix))
+        .map(|(_, compression)| compression))
+    }
+}
+
+/// Open a file for reading, decompressing it on the fly if it's compressed. Files made of
+/// several compressed streams one after another, as `cat a.gz b.gz` makes, are read whole.
+pub fn open(path: &Path) -> io::Result<Box<dyn Read>> {
+    let mut f = File::open(path)?;
+    let reader = BufReader::new(f.try_clone()?);
+
+    Ok(match Compression::detect(&mut f)? {
+        Some(Compression::Gzip) => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        Some(Compression::Zstd) => Box::new(zstd::Decoder::with_buffer(reader)?),
+        Some(Compression::Xz) => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        Some(Compression::Bzip2) => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+        None => Box::new(reader),
+    })
+}
+
+/// The rotated generations of a log that sit next to it, oldest first: `app.log.3.gz`,
+/// `app.log.2.gz`, `app.log.1` for `app.log`.
+pub fn rotated(path: &Path) -> Vec<PathBuf> {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return Vec::new();
+    };
+    let dir = crate::fspec::parent_dir(path);
+    let Ok(entries) = dir.read_dir() else {
+        return Vec::new();
+    };
+
+    let mut found: Vec<(u64, PathBuf)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let file_name = entry.file_name();
+            let rest = file_name.to_str()?.strip_prefix(name)?.strip_prefix('.')?;
+            let (generation, ext) = rest.split_once('.').unwrap_or((rest, ""));
+            if !ext.is_empty() && !EXTENSIONS.contains(&ext) {
+                return None;
+            }
+            // Keep the path as it was given, so it's named alike in errors.
+            Some((generation.parse().ok()?, path.with_file_name(&file_name)))
+        })
+        .collect();
+
+    found.sort_by_key(|(generation, _)| std::cmp::Reverse(*generation));
+    found.into_iter().map(|(_, path)| path).collect()
+}
@@ -0,0 +1,62 @@
+use std::collections::VecDeque;
+
+/// How many lines around each sieve match are printed with it, like grep's -B and -A.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Context {
+    pub before: usize,
+    pub after: usize,
+}
+
+impl Context {
+    pub fn is_empty(&self) -> bool {
+        self.before == 0 && self.after == 0
+    }
+}
+
+/// The recent lines of one file that didn't match, in case the next one does, and how many
+/// more lines are due after the last match.
+#[derive(Default)]
+pub struct Window {
+    held: VecDeque<Vec<u8>>,
+    after: usize,
+    // A line went by unprinted since the last one printed, so the next group is separated.
+    gap: bool,
+    printed: bool,
+}
+
+impl Window {
+    /// A line matched. Returns whether a separator goes before its group, and the held lines
+    /// to print ahead of it.
+    pub fn matched(&mut self, context: Context) -> (bool, Vec<Vec<u8>>) {
+        let separate = self.gap && self.printed;
+        self.gap = false;
+        self.printed = true;
+        self.after = context.after;
+        (separate, self.held.drain(..).collect())
+    }
+
+    /// A line didn't match. Returns it if it should be printed as after-context, otherwise
+    /// it is held as possible before-context.
+    pub fn unmatched(&mut self, line: &[u8], context: Context) -> bool {
+        if self.after > 0 {
+            self.after -= 1;
+            return true;
+        }
+
+        if self.held.len() == context.before {
+            self.gap = true;
+            self.held.pop_front();
+        }
+        if context.before > 0 {
+            self.held.push_back(line.to_vec());
+        }
+        false
+    }
+
+    /// Lines were dropped without being seen, so what is held no longer leads up to the next.
+    pub fn skip(&mut self) {
+        self.held.clear();
+        self.after = 0;
+        self.gap = true;
+    }
+}
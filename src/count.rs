@@ -0,0 +1,50 @@
+use std::str::FromStr;
+
+/// What `-n` and `-c` count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    Lines,
+    Bytes,
+}
+
+/// How much of the input is printed before it is followed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Count {
+    /// The last N lines or bytes.
+    Last(u64),
+    /// Everything from line or byte N on, counting from 1 like `tail -n +N`.
+    From(u64),
+}
+
+impl FromStr for Count {
+    type Err = String;
+
+    /// `N`, `-N` or `+N`, where N may end in K, M or G (powers of 1024) or KB, MB or GB
+    /// (powers of 1000).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, rest) = match s.strip_prefix('+') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('-').unwrap_or(s)),
+        };
+
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (n, suffix) = rest.split_at(digits);
+        let n: u64 = n.parse().map_err(|_| "not a number".to_string())?;
+
+        let scale: u64 = match suffix {
+            "" => 1,
+            "K" | "k" => 1 << 10,
+            "M" => 1 << 20,
+            "G" => 1 << 30,
+            "KB" | "kB" => 1000,
+            "MB" => 1000 * 1000,
+            "GB" => 1000 * 1000 * 1000,
+            _ => return Err(format!("unknown suffix '{}'", suffix)),
+        };
+        let n = n.checked_mul(scale).ok_or("too large")?;
+
+        Ok(if from { Count::From(n) } else { Count::Last(n) })
+    }
+}
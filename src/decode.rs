@@ -0,0 +1,151 @@
+use std::{borrow::Cow, fmt::Write};
+
+use clap::ValueEnum;
+
+/// Character encoding of the input. Lines are split on the encoding's own newline and
+/// decoded to UTF-8 before they are sieved or printed.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    #[value(name = "utf-8", alias = "utf8")]
+    Utf8,
+    #[value(name = "utf-16le", alias = "utf16le")]
+    Utf16le,
+    #[value(name = "latin1", alias = "iso-8859-1")]
+    Latin1,
+}
+
+impl Encoding {
+    /// Length in bytes of a newline.
+    pub fn newline_len(self) -> usize {
+        match self {
+            Encoding::Utf16le => 2,
+            _ => 1,
+        }
+    }
+
+    fn is_newline_at(self, buf: &[u8], i: usize, base: u64) -> bool {
+        match self {
+            // Only code units on an even file offset count, so 0x0A in a high byte doesn't.
+            Encoding::Utf16le => {
+                buf[i] == b'\n' && buf.get(i + 1) == Some(&0) && (base + i as u64).is_multiple_of(2)
+            }
+            _ => buf[i] == b'\n',
+        }
+    }
+
+    /// Round a byte offset up to the start of a character's code unit.
+    pub fn align(self, offset: u64) -> u64 {
+        match self {
+            Encoding::Utf16le => offset.next_multiple_of(2),
+            _ => offset,
+        }
+    }
+
+    /// Index of the first newline in `buf`, whose first byte is at file offset `base`.
+    pub fn find_newline(self, buf: &[u8], base: u64) -> Option<usize> {
+        memchr::memchr_iter(b'\n', buf).find(|&i| self.is_newline_at(buf, i, base))
+    }
+
+    /// Index of the last newline in `buf`, whose first byte is at file offset `base`.
+    pub fn rfind_newline(self, buf: &[u8], base: u64) -> Option<usize> {
+        memchr::memrchr_iter(b'\n', buf).find(|&i| self.is_newline_at(buf, i, base))
+    }
+
+    /// Split `buf`, whose first byte is at file offset `base`, into lines without their
+    /// newlines. A trailing newline doesn't start another line.
+    pub fn split_lines(self, buf: &[u8], base: u64) -> Vec<&[u8]> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+
+        for i in memchr::memchr_iter(b'\n', buf) {
+            if i >= start && self.is_newline_at(buf, i, base) {
+                lines.push(&buf[start..i]);
+                start = i + self.newline_len();
+            }
+        }
+
+        if start < buf.len() {
+            lines.push(&buf[start..]);
+        }
+        lines
+    }
+
+    /// Decode one line to UTF-8. UTF-8 input is passed through as is, invalid bytes included,
+    /// so that --binary decides what happens to them. A trailing carriage return is dropped.
+    pub fn decode(self, raw: &[u8]) -> Cow<'_, [u8]> {
+        let line: Cow<[u8]> = match self {
+            Encoding::Utf8 => Cow::Borrowed(raw),
+            Encoding::Latin1 => {
+                // Latin-1 bytes are exactly the first 256 code points.
+                Cow::Owned(
+                    raw.iter()
+                        .map(|&b| b as char)
+                        .collect::<String>()
+                        .into_bytes(),
+                )
+            }
+            Encoding::Utf16le => {
+                let units = raw
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_le_bytes([pair[0], pair[1]]));
+                let text: String = char::decode_utf16(units)
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .filter(|&c| c != '\u{feff}')
+                    .collect();
+                Cow::Owned(text.into_bytes())
+            }
+        };
+
+        match line {
+            Cow::Borrowed(l) => Cow::Borrowed(l.strip_suffix(b"\r").unwrap_or(l)),
+            Cow::Owned(mut l) => {
+                if l.last() == Some(&b'\r') {
+                    l.pop();
+                }
+                Cow::Owned(l)
+            }
+        }
+    }
+}
+
+/// What to do with bytes that aren't valid UTF-8.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Binary {
+    /// Replace invalid sequences with U+FFFD.
+    #[default]
+    Lossy,
+    /// Print invalid bytes and control characters as \xNN.
+    Escape,
+    /// Drop lines that aren't valid UTF-8.
+    Skip,
+}
+
+impl Binary {
+    pub fn keep(self, line: &[u8]) -> bool {
+        self != Binary::Skip || std::str::from_utf8(line).is_ok()
+    }
+
+    /// Make bytes printable.
+    pub fn display(self, bytes: &[u8]) -> Cow<'_, str> {
+        match self {
+            Binary::Lossy | Binary::Skip => String::from_utf8_lossy(bytes),
+            Binary::Escape => {
+                let mut out = String::with_capacity(bytes.len());
+                for chunk in bytes.utf8_chunks() {
+                    for c in chunk.valid().chars() {
+                        if c.is_control() && c != '\t' && (c as u32) < 0x100 {
+                            let _ = write!(out, "\\x{:02x}", c as u32);
+                        } else {
+                            out.push(c);
+                        }
+                    }
+                    for b in chunk.invalid() {
+                        let _ = write!(out, "\\x{:02x}", b);
+                    }
+                }
+                Cow::Owned(out)
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::{fmt, io};
+
+/// What can go wrong once the arguments have been parsed. Each error names the input it
+/// happened on; they are printed as `trunk: <error>`.
+#[derive(Debug)]
+pub enum Error {
+    Open { name: String, source: io::Error },
+    Read { name: String, source: io::Error },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn open(name: &str, source: io::Error) -> Self {
+        Error::Open {
+            name: name.to_string(),
+            source,
+        }
+    }
+
+    pub fn read(name: &str, source: io::Error) -> Self {
+        Error::Read {
+            name: name.to_string(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Open { name, source } => write!(f, "cannot open '{}': {}", name, source),
+            Error::Read { name, source } => write!(f, "cannot read '{}': {}", name, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Open { source, .. } | Error::Read { source, .. } => Some(source),
+        }
+    }
+}
@@ -0,0 +1,449 @@
+use std::{cell::RefCell, cmp::Ordering, str::FromStr};
+
+use clap::ValueEnum;
+use colored::{Color, Colorize};
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::level::Level;
+
+// Field names that hold a record's time, level and message, which are printed bare and first.
+const TIME_KEYS: [&str; 6] = [
+    "ts",
+    "time",
+    "timestamp",
+    "@timestamp",
+    "t",
+    "__REALTIME_TIMESTAMP",
+];
+const LEVEL_KEYS: [&str; 5] = ["level", "lvl", "severity", "log.level", "PRIORITY"];
+const MESSAGE_KEYS: [&str; 5] = ["msg", "message", "@message", "event", "MESSAGE"];
+
+/// A line's fields by name, once parsed.
+pub type Record = Map<String, Value>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Match,
+}
+
+/// A condition on a field, like `level=error` or `latency_ms>500`. Values are compared as
+/// numbers when both sides are numbers, and as text otherwise; `~` matches a regex.
+#[derive(Clone, Debug)]
+pub struct Predicate {
+    field: String,
+    op: Op,
+    value: String,
+    regex: Option<Regex>,
+}
+
+impl FromStr for Predicate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let at = s
+            .find(['=', '!', '<', '>', '~'])
+            .ok_or("expected FIELD=VALUE, or one of != > >= < <= ~")?;
+        let (field, rest) = s.split_at(at);
+
+        let (op, value) = [
+            ("!=", Op::Ne),
+            (">=", Op::Ge),
+            ("<=", Op::Le),
+            ("=", Op::Eq),
+            (">", Op::Gt),
+            ("<", Op::Lt),
+            ("~", Op::Match),
+        ]
+        .into_iter()
+        .find_map(|(token, op)| rest.strip_prefix(token).map(|value| (op, value)))
+        .ok_or_else(|| format!("unknown operator in '{}'", s))?;
+
+        if field.is_empty() {
+            return Err("missing field name".to_string());
+        }
+        let regex = match op {
+            Op::Match => Some(Regex::new(value).map_err(|e| e.to_string())?),
+            _ => None,
+        };
+
+        Ok(Self {
+            field: field.to_string(),
+            op,
+            value: value.to_string(),
+            regex,
+        })
+    }
+}
+
+impl Predicate {
+    /// A record without the field never matches.
+    fn matches(&self, record: &Record) -> bool {
+        let Some(actual) = lookup(record, &self.field) else {
+            return false;
+        };
+        let text = plain(actual);
+
+        if let Some(regex) = &self.regex {
+            return regex.is_match(&text);
+        }
+
+        let ordering = match (text.parse::<f64>(), self.value.parse::<f64>()) {
+            (Ok(a), Ok(b)) => a.partial_cmp(&b),
+            _ => Some(text.as_str().cmp(&self.value)),
+        };
+        let Some(ordering) = ordering else {
+            return false;
+        };
+
+        match self.op {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Ne => ordering != Ordering::Equal,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Match => unreachable!("matched by regex above"),
+        }
+    }
+}
+
+/// How lines are split into fields.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One JSON object per line.
+    Json,
+    /// `key=value` pairs separated by spaces, with values quoted when they need to be.
+    Logfmt,
+    /// `journalctl -o json` output.
+    Journal,
+    /// Syslog lines: time, host, program[pid]: message.
+    Syslog,
+    /// nginx's combined access log.
+    Nginx,
+    /// Apache's combined access log.
+    Apache,
+    /// The Common Log Format.
+    Clf,
+}
+
+// Access logs: host ident user [time] "request" status bytes, then referer and user agent in
+// the combined format.
+const CLF: &str = r#"^(?P<host>\S+) (?P<ident>\S+) (?P<user>\S+) \[(?P<time>[^\]]+)\] "(?P<method>[A-Z]+) (?P<path>\S+)(?: (?P<protocol>[^"]*))?" (?P<status>\d{3}) (?P<bytes>\d+|-)"#;
+const COMBINED: &str = r#" "(?P<referer>(?:[^"\\]|\\.)*)" "(?P<agent>(?:[^"\\]|\\.)*)""#;
+const SYSLOG: &str = r"^(?P<time>[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}|\d{4}-\d{2}-\d{2}T\S+) (?P<host>\S+) (?P<program>[^\s:\[]+)(?:\[(?P<pid>\d+)\])?: (?P<msg>.*)$";
+
+impl Format {
+    // Fixed layouts are matched by a regex whose named groups are the fields, in the order
+    // they're printed as columns.
+    fn pattern(self) -> Option<String> {
+        match self {
+            Format::Json | Format::Logfmt | Format::Journal => None,
+            Format::Syslog => Some(SYSLOG.to_string()),
+            Format::Nginx | Format::Apache => Some(format!("{}(?:{})?", CLF, COMBINED)),
+            Format::Clf => Some(CLF.to_string()),
+        }
+    }
+}
+
+/// Structured log mode. Lines that parse in the format are filtered on their fields and
+/// printed in a compact layout; any other line goes through as it is.
+pub struct Fields {
+    format: Format,
+    pattern: Option<Regex>,
+    predicates: Vec<Predicate>,
+    fields: Vec<String>,
+    // Widest value seen in each column so far, so columns line up as lines stream in.
+    widths: RefCell<Vec<usize>>,
+}
+
+impl Fields {
+    pub fn new(format: Format, predicates: Vec<Predicate>, fields: Vec<String>) -> Self {
+        Self {
+            format,
+            // The patterns are fixed and known to compile.
+            pattern: format.pattern().map(|re| Regex::new(&re).unwrap()),
+            predicates,
+            fields,
+            widths: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The fields of a line, if it parses in the format.
+    pub fn parse(&self, line: &[u8]) -> Option<Record> {
+        match (&self.pattern, self.format) {
+            (Some(pattern), _) => parse_pattern(pattern, line),
+            (None, Format::Logfmt) => parse_logfmt(line),
+            (None, _) => parse_json(line),
+        }
+    }
+
+    /// Whether any field predicates were given.
+    pub fn filters(&self) -> bool {
+        !self.predicates.is_empty()
+    }
+
+    /// Whether a parsed line satisfies every predicate.
+    pub fn keeps(&self, record: &Record) -> bool {
+        self.predicates.iter().all(|p| p.matches(record))
+    }
+
+    /// The level in a parsed line's level field, if it has one.
+    pub fn level(record: &Record) -> Option<Level> {
+        let (_, value) = record
+            .iter()
+            .find(|(key, _)| role(key) == Some(Role::Level))?;
+        Level::parse(&plain(value))
+    }
+
+    /// The compact form of a parsed line.
+    pub fn render(&self, record: &Record) -> String {
+        match &self.pattern {
+            Some(pattern) => self.columns(pattern, record),
+            None => self.pairs(record),
+        }
+    }
+
+    // Time, level and message bare, then `key=value` for the other fields. With `--fields`
+    // only those are printed, in that order.
+    fn pairs(&self, record: &Record) -> String {
+        let keys: Vec<&str> = if self.fields.is_empty() {
+            let (roles, rest): (Vec<&str>, Vec<&str>) = record
+                .keys()
+                .map(String::as_str)
+                .partition(|key| role(key).is_some());
+            let mut roles = roles;
+            roles.sort_by_key(|key| role(key));
+            roles.into_iter().chain(rest).collect()
+        } else {
+            self.fields.iter().map(String::as_str).collect()
+        };
+
+        let parts: Vec<String> = keys
+            .into_iter()
+            .filter_map(|key| {
+                let value = lookup(record, key)?;
+                Some(match role(key) {
+                    Some(_) => paint(key, plain(value)),
+                    None => format!("{}={}", key.cyan(), coloured(value)),
+                })
+            })
+            .collect();
+
+        parts.join(" ")
+    }
+
+    // The values alone, one column per field of the pattern, or per `--fields`. Missing
+    // values are shown as `-` so the columns stay put.
+    fn columns(&self, pattern: &Regex, record: &Record) -> String {
+        let keys: Vec<&str> = if self.fields.is_empty() {
+            pattern.capture_names().flatten().collect()
+        } else {
+            self.fields.iter().map(String::as_str).collect()
+        };
+
+        let mut widths = self.widths.borrow_mut();
+        if widths.len() < keys.len() {
+            widths.resize(keys.len(), 0);
+        }
+
+        let mut out = String::new();
+        for (i, key) in keys.iter().enumerate() {
+            let (text, len) = match lookup(record, key).map(plain) {
+                Some(text) => {
+                    let len = text.chars().count();
+                    (paint(key, text), len)
+                }
+                None => ("-".dimmed().to_string(), 1),
+            };
+            widths[i] = widths[i].max(len);
+
+            if i > 0 {
+                out.push(' ');
+            }
+            out.push_str(&text);
+            if i + 1 < keys.len() {
+                out.push_str(&" ".repeat(widths[i] - len));
+            }
+        }
+
+        out.trim_end().to_string()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Role {
+    Time,
+    Level,
+    Message,
+}
+
+fn role(key: &str) -> Option<Role> {
+    if TIME_KEYS.contains(&key) {
+        Some(Role::Time)
+    } else if LEVEL_KEYS.contains(&key) {
+        Some(Role::Level)
+    } else if MESSAGE_KEYS.contains(&key) {
+        Some(Role::Message)
+    } else {
+        None
+    }
+}
+
+// A field's value coloured by what it holds: times are dimmed, and levels and HTTP statuses
+// coloured by severity.
+fn paint(key: &str, text: String) -> String {
+    let color = match role(key) {
+        _ if text.is_empty() => return text,
+        Some(Role::Time) => return text.dimmed().to_string(),
+        Some(Role::Level) => Level::parse(&text).map(Level::color),
+        Some(Role::Message) => None,
+        None if key == "status" => status_color(&text),
+        None => None,
+    };
+    match color {
+        Some(color) => text.color(color).bold().to_string(),
+        None => text,
+    }
+}
+
+fn status_color(status: &str) -> Option<Color> {
+    match status.as_bytes().first()? {
+        b'5' => Some(Color::Red),
+        b'4' => Some(Color::Yellow),
+        b'3' => Some(Color::Cyan),
+        b'2' => Some(Color::Green),
+        _ => None,
+    }
+}
+
+fn parse_json(line: &[u8]) -> Option<Record> {
+    match serde_json::from_slice(line).ok()? {
+        Value::Object(record) => Some(record),
+        _ => None,
+    }
+}
+
+// A line in one of the fixed layouts. Fields logged as `-` are left out.
+fn parse_pattern(pattern: &Regex, line: &[u8]) -> Option<Record> {
+    let line = std::str::from_utf8(line).ok()?;
+    let caps = pattern.captures(line)?;
+
+    let mut record = Map::new();
+    for name in pattern.capture_names().flatten() {
+        if let Some(m) = caps.name(name).filter(|m| m.as_str() != "-") {
+            record.insert(name.to_string(), typed(m.as_str()));
+        }
+    }
+    Some(record)
+}
+
+/// A logfmt line, e.g. `level=info msg="listening on :80" dur=12ms`. Every word has to be a
+/// `key=value` pair, so prose that happens to contain an `=` isn't taken for fields. Values
+/// that look like numbers or booleans are typed as such.
+fn parse_logfmt(line: &[u8]) -> Option<Record> {
+    let line = std::str::from_utf8(line).ok()?;
+    let mut record = Map::new();
+    let mut rest = line.trim_start();
+
+    while !rest.is_empty() {
+        let eq = rest.find('=')?;
+        let key = &rest[..eq];
+        if key.is_empty() || key.contains(char::is_whitespace) || key.contains('"') {
+            return None;
+        }
+        rest = &rest[eq + 1..];
+
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let (value, len) = unquote(quoted)?;
+            rest = &quoted[len..];
+            if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+                return None;
+            }
+            Value::String(value)
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let word = &rest[..end];
+            rest = &rest[end..];
+            if word.contains('"') {
+                return None;
+            }
+            typed(word)
+        };
+
+        record.insert(key.to_string(), value);
+        rest = rest.trim_start();
+    }
+
+    (!record.is_empty()).then_some(record)
+}
+
+// The text of a quoted logfmt value, which starts just after its opening quote, and the
+// length up to and including the closing quote.
+fn unquote(s: &str) -> Option<(String, usize)> {
+    let mut value = String::new();
+    let mut chars = s.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, i + 1)),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                other => value.push(other),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+fn typed(word: &str) -> Value {
+    match word {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => match serde_json::from_str::<serde_json::Number>(word) {
+            Ok(n) => Value::Number(n),
+            Err(_) => Value::String(word.to_string()),
+        },
+    }
+}
+
+/// A field by name, or by a dotted path into nested objects, e.g. `http.status`.
+fn lookup<'a>(record: &'a Record, field: &str) -> Option<&'a Value> {
+    if let Some(value) = record.get(field) {
+        return Some(value);
+    }
+
+    let mut parts = field.split('.');
+    let mut value = record.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_object()?.get(part)?;
+    }
+    Some(value)
+}
+
+// Strings without their quotes, anything else as JSON.
+fn plain(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+// Strings are only quoted if they'd be ambiguous in a `key=value` list. Numbers, booleans
+// and null are coloured so they stand out from text.
+fn coloured(value: &Value) -> String {
+    match value {
+        Value::String(s) if !s.is_empty() && !s.contains([' ', '=', '"']) => s.clone(),
+        Value::String(_) | Value::Object(_) | Value::Array(_) => value.to_string(),
+        Value::Number(_) | Value::Bool(_) | Value::Null => value.to_string().yellow().to_string(),
+    }
+}
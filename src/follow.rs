@@ -0,0 +1,609 @@
+use std::{
+    fmt,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use crossterm::{event, terminal};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::context::Context;
+use crate::count::{Count, Unit};
+use crate::error::Error;
+use crate::fspec::{parent_dir, FileSpec, PathState};
+use crate::printer::{Line, Printer};
+use crate::records::Pending;
+use crate::sieve::Sieve;
+use crate::tail::Tail;
+
+// How often files that are missing under --retry are checked for, when no event says so.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+// Poll interval used when falling back from native events. Polling is done by the event
+// loop itself, stat-ing every file each interval; notify's PollWatcher only compares mtimes
+// to the second, which misses most appends to a busy log.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Piped input that goes quiet for this long is taken to have caught up, and the tail of what
+// came so far is printed before the rest is followed line by line.
+const STDIN_SETTLE: Duration = Duration::from_millis(200);
+
+// Filesystems whose changes made on other hosts never reach inotify.
+#[cfg(target_os = "linux")]
+const REMOTE_FILESYSTEMS: [&str; 9] = [
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "9p", "afs", "ceph", "fuse",
+];
+
+/// How a followed file is tracked.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FollowMode {
+    /// Keep reading the open file, even if it is renamed.
+    Descriptor,
+    /// Reopen the path when the file behind it is rotated or re-created.
+    Name,
+}
+
+/// How the files are followed, from the command line.
+pub struct Options {
+    pub mode: FollowMode,
+    pub poll: Option<Duration>,
+    pub partial_timeout: Option<Duration>,
+    // Follow standard input, printing its backlog as counted by `unit` and `count` once it
+    // has caught up.
+    pub stdin: bool,
+    pub unit: Unit,
+    pub count: Count,
+    pub context: Context,
+}
+
+/// Everything the event loop waits on.
+enum Message {
+    Fs(notify::Result<Event>),
+    // A chunk of piped input, or None once it is closed.
+    Stdin(Option<Vec<u8>>),
+    // A key press or resize, for the pager.
+    Input(event::Event),
+    // A problem met on another thread, to be reported through the printer.
+    Warning(String),
+    Stop,
+}
+
+/// The last lines of piped input that got through the sieve, held until the input catches up.
+struct Backlog {
+    tail: Tail,
+    // The record being gathered, when lines are grouped.
+    pending: Pending,
+    last_input: Instant,
+}
+
+impl Backlog {
+    fn new(unit: Unit, count: Count, context: Context) -> Self {
+        Self {
+            tail: Tail::new(unit, count, context),
+            pending: Pending::default(),
+            last_input: Instant::now(),
+        }
+    }
+}
+
+/// Printed to stderr when following ends.
+pub struct Summary {
+    elapsed: Duration,
+    read: u64,
+    printed: u64,
+    // Whether following was stopped by SIGINT or SIGTERM.
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
+    interrupted: bool,
+}
+
+impl Summary {
+    #[cfg(feature = "tui")]
+    pub fn interrupted(&self) -> bool {
+        self.interrupted
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "trunk: followed for {:.1}s, {} lines read, {} printed",
+            self.elapsed.as_secs_f64(),
+            self.read,
+            self.printed
+        )
+    }
+}
+
+/// Follow the files until interrupted. The loop blocks on a channel fed by the watcher, the
+/// stdin reader and the signal handler, only waking on a timer to poll, while a file is waiting
+/// to be created, while piped input is settling, or while a partial line is waiting to be flushed.
+pub fn run(
+    specs: &mut [FileSpec],
+    printer: &mut Printer,
+    sieve: Option<&Sieve>,
+    options: &Options,
+) -> Summary {
+    let mode = options.mode;
+    let started = Instant::now();
+    let printed = printer.printed;
+    let (tx, rx) = mpsc::channel();
+
+    let fs_tx = tx.clone();
+    let (_watcher, polled) = watch(specs, mode, options.poll, printer, move |res| {
+        let _ = fs_tx.send(Message::Fs(res));
+    });
+    let poll = polled
+        .contains(&true)
+        .then(|| options.poll.unwrap_or(DEFAULT_POLL_INTERVAL));
+
+    let stdin = specs
+        .iter()
+        .position(|s| s.fpath.is_none())
+        .filter(|_| options.stdin);
+    let mut backlog = stdin.map(|_| Backlog::new(options.unit, options.count, options.context));
+    if stdin.is_some() {
+        let stdin_tx = tx.clone();
+        thread::spawn(move || read_stdin(stdin_tx));
+    }
+
+    // Paged output takes keys, read a key at a time rather than a line.
+    let paged = printer.paged()
+        && match terminal::enable_raw_mode() {
+            Ok(()) => true,
+            Err(e) => {
+                printer.warn(&format!("cannot read keys: {}", e));
+                false
+            }
+        };
+    if paged {
+        let keys_tx = tx.clone();
+        thread::spawn(move || read_keys(keys_tx));
+    }
+
+    // Covers SIGINT and SIGTERM.
+    if let Err(e) = ctrlc::set_handler(move || {
+        let _ = tx.send(Message::Stop);
+    }) {
+        printer.warn(&format!("cannot install signal handler: {}", e));
+    }
+
+    // Catch anything written between the backlog being read and the watches being set up.
+    let mut read = 0;
+    for (idx, fspec) in specs.iter_mut().enumerate() {
+        read += follow_filter(idx, fspec, printer, sieve, mode);
+    }
+    printer.flush();
+
+    let mut interrupted = false;
+    loop {
+        let settle = backlog
+            .as_ref()
+            .map(|b| STDIN_SETTLE.saturating_sub(b.last_input.elapsed()))
+            .into_iter()
+            .chain(printer.record_timeout())
+            .chain(printer.merge_timeout())
+            .min();
+        let msg = match next_timeout(specs, options.partial_timeout, poll, settle) {
+            Some(timeout) => rx.recv_timeout(timeout),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match msg {
+            Ok(Message::Fs(Ok(event))) if matches!(event.kind, EventKind::Access(_)) => {}
+            Ok(Message::Fs(Ok(event))) => {
+                // Events are routed back to the file they name; if they name none of ours
+                // (e.g. a rotated-away file), every file is checked.
+                let named: Vec<bool> = specs
+                    .iter()
+                    .map(|s| event.paths.iter().any(|p| s.is_path(p)))
+                    .collect();
+                let any_named = named.contains(&true);
+
+                for (idx, fspec) in specs.iter_mut().enumerate() {
+                    if named[idx] || !any_named {
+                        read += follow_filter(idx, fspec, printer, sieve, mode);
+                    }
+                }
+            }
+            Ok(Message::Fs(Err(e))) => printer.warn(&format!("watch error: {}", e)),
+            Ok(Message::Warning(message)) => printer.warn(&message),
+            Ok(Message::Stdin(Some(chunk))) => {
+                let Some(idx) = stdin else { continue };
+                for line in specs[idx].push(&chunk) {
+                    read += 1;
+                    emit_stdin(idx, line, &mut backlog, printer, sieve);
+                }
+                if let Some(b) = &mut backlog {
+                    b.last_input = Instant::now();
+                }
+            }
+            Ok(Message::Stdin(None)) => break,
+            Ok(Message::Input(event)) => {
+                if printer.input(&event) {
+                    break;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                if backlog
+                    .as_ref()
+                    .is_some_and(|b| b.last_input.elapsed() >= STDIN_SETTLE)
+                {
+                    print_backlog(stdin, &mut backlog, printer, sieve);
+                }
+                printer.finish_records(false, sieve);
+
+                for (idx, fspec) in specs.iter_mut().enumerate() {
+                    if polled[idx] || (fspec.fpath.is_some() && !fspec.is_open()) {
+                        read += follow_filter(idx, fspec, printer, sieve, mode);
+                    }
+
+                    let expired = fspec.partial_since.zip(options.partial_timeout);
+                    if expired.is_some_and(|(since, timeout)| since.elapsed() >= timeout) {
+                        // A fragment of piped input mustn't jump ahead of the lines before it.
+                        if stdin == Some(idx) {
+                            print_backlog(stdin, &mut backlog, printer, sieve);
+                        }
+                        read += flush_partial(idx, fspec, printer, sieve);
+                    }
+                }
+            }
+            Ok(Message::Stop) => {
+                interrupted = true;
+                break;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        printer.flush();
+    }
+
+    // Whatever piped input was still settling or waiting for a newline is due now.
+    if let Some(idx) = stdin {
+        let partial = specs[idx].take_partial();
+        if !partial.is_empty() {
+            read += 1;
+            emit_stdin(idx, partial, &mut backlog, printer, sieve);
+        }
+        print_backlog(stdin, &mut backlog, printer, sieve);
+    }
+
+    for (idx, fspec) in specs.iter_mut().enumerate() {
+        read += flush_partial(idx, fspec, printer, sieve);
+    }
+    printer.finish_records(true, sieve);
+    printer.flush_all();
+    if paged {
+        printer.unpause();
+        let _ = terminal::disable_raw_mode();
+    }
+
+    Summary {
+        elapsed: started.elapsed(),
+        read,
+        printed: printer.printed - printed,
+        interrupted,
+    }
+}
+
+/// Set up a native watcher on the files' paths. Returns it, with which of the files have to
+/// be polled instead: all of them if that was asked for, a path lives on a network filesystem
+/// or events aren't available, and otherwise just those whose path can't be watched.
+fn watch<F>(
+    specs: &[FileSpec],
+    mode: FollowMode,
+    poll: Option<Duration>,
+    printer: &mut Printer,
+    handler: F,
+) -> (Option<RecommendedWatcher>, Vec<bool>)
+where
+    F: Fn(notify::Result<Event>) + Send + 'static,
+{
+    let all = specs.iter().map(|s| s.fpath.is_some()).collect();
+    if poll.is_some() {
+        return (None, all);
+    }
+
+    let paths = watch_paths(specs, mode);
+    if let Some(path) = paths.iter().flatten().find(|p| is_remote(p)) {
+        printer.warn(&format!(
+            "'{}' is on a network filesystem; polling for changes",
+            path.display()
+        ));
+        return (None, all);
+    }
+
+    let mut watcher = match notify::recommended_watcher(handler) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            printer.warn(&format!("cannot watch for events ({}); polling instead", e));
+            return (None, all);
+        }
+    };
+
+    let mut watched: Vec<PathBuf> = Vec::new();
+    let polled = paths
+        .into_iter()
+        .map(|path| {
+            let Some(path) = path else { return false };
+            if watched.contains(&path) {
+                return false;
+            }
+
+            // A file that isn't there yet is watched for in its directory.
+            let res = watcher
+                .watch(&path, RecursiveMode::NonRecursive)
+                .map(|_| path.clone())
+                .or_else(|e| {
+                    let dir = parent_dir(&path);
+                    match watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                        Ok(()) => Ok(dir),
+                        Err(_) => Err(e),
+                    }
+                });
+            match res {
+                Ok(watched_path) => {
+                    watched.push(path);
+                    watched.push(watched_path);
+                    false
+                }
+                Err(e) => {
+                    printer.warn(&format!(
+                        "cannot watch '{}' ({}); polling it instead",
+                        path.display(),
+                        e
+                    ));
+                    true
+                }
+            }
+        })
+        .collect();
+
+    (Some(watcher), polled)
+}
+
+/// Whether the path is on a filesystem that inotify can't see remote changes on, going by
+/// the longest matching mount point in /proc/self/mounts.
+#[cfg(target_os = "linux")]
+fn is_remote(path: &Path) -> bool {
+    let Ok(path) = path.canonicalize() else {
+        return false;
+    };
+    let Ok(mounts) = std::fs::read_to_string("/proc/self/mounts") else {
+        return false;
+    };
+
+    let fstype = mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _source = fields.next()?;
+            let target = fields.next()?.replace("\\040", " ");
+            let fstype = fields.next()?;
+            path.starts_with(&target).then_some((target.len(), fstype))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, fstype)| fstype);
+
+    fstype.is_some_and(|t| {
+        REMOTE_FILESYSTEMS
+            .iter()
+            .any(|r| t == *r || t.starts_with(&format!("{}.", r)))
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_remote(_path: &Path) -> bool {
+    false
+}
+
+/// How long the event loop may block before a timer is due, or None to wait on events alone.
+fn next_timeout(
+    specs: &[FileSpec],
+    partial_timeout: Option<Duration>,
+    poll: Option<Duration>,
+    settle: Option<Duration>,
+) -> Option<Duration> {
+    let retry = specs
+        .iter()
+        .any(|s| s.fpath.is_some() && !s.is_open())
+        .then_some(RETRY_INTERVAL);
+
+    let flush = partial_timeout.and_then(|timeout| {
+        specs
+            .iter()
+            .filter_map(|s| s.partial_since)
+            .map(|since| timeout.saturating_sub(since.elapsed()))
+            .min()
+    });
+
+    retry
+        .into_iter()
+        .chain(flush)
+        .chain(poll)
+        .chain(settle)
+        .min()
+}
+
+/// Forward piped input to the event loop as it arrives, then report it closed.
+fn read_stdin(tx: Sender<Message>) {
+    let mut input = io::stdin().lock();
+    let mut buf = vec![0; 64 * 1024];
+
+    loop {
+        match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                if tx.send(Message::Stdin(Some(buf[..n].to_vec()))).is_err() {
+                    return;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => {
+                let message = Error::read("standard input", e).to_string();
+                let _ = tx.send(Message::Warning(message));
+                break;
+            }
+        }
+    }
+
+    let _ = tx.send(Message::Stdin(None));
+}
+
+/// Forward key presses and resizes to the event loop.
+fn read_keys(tx: Sender<Message>) {
+    while let Ok(event) = event::read() {
+        if tx.send(Message::Input(event)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Print a line of piped input, or hold it back while its backlog is still being gathered.
+fn emit_stdin(
+    idx: usize,
+    line: Vec<u8>,
+    backlog: &mut Option<Backlog>,
+    printer: &mut Printer,
+    sieve: Option<&Sieve>,
+) {
+    match backlog {
+        Some(backlog) => {
+            let Some(record) = printer.gather(&mut backlog.pending, line) else {
+                return;
+            };
+            let record = Line::new(record);
+            let passes = printer.passes(&record, sieve);
+            if let Some(record) = backlog.tail.push(record, passes) {
+                printer.emit_held(idx, record, sieve);
+            }
+        }
+        None => printer.emit(idx, &line, sieve),
+    }
+}
+
+/// Print the backlog of piped input, after which its lines are printed as they arrive.
+fn print_backlog(
+    stdin: Option<usize>,
+    backlog: &mut Option<Backlog>,
+    printer: &mut Printer,
+    sieve: Option<&Sieve>,
+) {
+    let (Some(idx), Some(mut backlog)) = (stdin, backlog.take()) else {
+        return;
+    };
+
+    // The record being gathered is as complete as the backlog gets.
+    if let Some(record) = backlog.pending.take() {
+        let record = Line::new(record);
+        let passes = printer.passes(&record, sieve);
+        if let Some(record) = backlog.tail.push(record, passes) {
+            printer.emit_held(idx, record, sieve);
+        }
+    }
+
+    for line in backlog.tail {
+        match line {
+            Some(line) => printer.emit_held(idx, line, sieve),
+            None => printer.skip(idx),
+        }
+    }
+}
+
+fn watch_paths(specs: &[FileSpec], mode: FollowMode) -> Vec<Option<PathBuf>> {
+    specs
+        .iter()
+        .map(|s| match mode {
+            FollowMode::Descriptor => s.fpath.clone(),
+            // Following by name watches the directories, so renames and re-creations are seen.
+            FollowMode::Name => s.canon.as_deref().or(s.fpath.as_deref()).map(parent_dir),
+        })
+        .collect()
+}
+
+/// Print whatever was appended to the file since the last call. Returns the number of lines read.
+fn follow_filter(
+    idx: usize,
+    file: &mut FileSpec,
+    printer: &mut Printer,
+    sieve: Option<&Sieve>,
+    mode: FollowMode,
+) -> u64 {
+    let mut read = 0;
+
+    if !file.is_open() {
+        // Waiting on --retry for the file to (re)appear.
+        if !file.open() {
+            return read;
+        }
+        printer.warn(&format!("'{}' has appeared; following new file", file.name));
+        file.size = 0;
+    }
+
+    // A compressed file is only read for its backlog.
+    if file.compressed {
+        return read;
+    }
+
+    if file.open_len() >= file.size {
+        // Regular tail -f behaviour so far.
+        // Start filtering things out here...
+        match file.read_lines() {
+            Ok(lines) => {
+                for line in lines {
+                    read += 1;
+                    printer.emit(idx, &line, sieve);
+                }
+            }
+            Err(e) => printer.warn(&e.to_string()),
+        }
+    } else {
+        // Whatever was waiting for a newline won't get one now.
+        read += flush_partial(idx, file, printer, sieve);
+
+        // Display message informing that the file size reduced since the last output. continue following new EOF
+        printer.banner(idx, "***FILE TRUNCATED: READING FROM NEW EOF***");
+        file.update_size();
+    }
+
+    if mode == FollowMode::Name {
+        // The old file has been drained above, so it's safe to move on to its replacement.
+        match file.path_state() {
+            PathState::Same => {}
+            PathState::Missing => {}
+            PathState::Replaced => {
+                read += flush_partial(idx, file, printer, sieve);
+                printer.warn(&format!(
+                    "'{}' has been replaced; following new file",
+                    file.name
+                ));
+                if file.open() {
+                    file.size = 0;
+                    read += follow_filter(idx, file, printer, sieve, mode);
+                }
+            }
+        }
+    }
+
+    read
+}
+
+/// Print the file's unfinished line as it stands. Returns the number of lines read.
+fn flush_partial(
+    idx: usize,
+    file: &mut FileSpec,
+    printer: &mut Printer,
+    sieve: Option<&Sieve>,
+) -> u64 {
+    let partial = file.take_partial();
+    if partial.is_empty() {
+        return 0;
+    }
+
+    printer.emit(idx, &partial, sieve);
+    1
+}
@@ -0,0 +1,95 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+use crate::decode::Encoding;
+
+// How much of the file is read per step.
+const BLOCK_SIZE: u64 = 64 * 1024;
+
+/// Walks a file from `start` to `end`, yielding `(offset, line)` for each line without its
+/// newline, a block at a time. `start` is taken to be the beginning of a line, and a line
+/// cut off by `end` is yielded as it stands.
+pub struct FwdLines<'a> {
+    f: &'a mut File,
+    // File offset of `buf[head]`, where the next line starts.
+    pos: u64,
+    end: u64,
+    buf: Vec<u8>,
+    head: usize,
+    // Index into `buf` up to which no newline was found, so long lines aren't rescanned.
+    scanned: usize,
+    encoding: Encoding,
+}
+
+impl<'a> FwdLines<'a> {
+    pub fn new(f: &'a mut File, start: u64, end: u64, encoding: Encoding) -> io::Result<Self> {
+        f.seek(SeekFrom::Start(start))?;
+        Ok(Self {
+            f,
+            pos: start,
+            end,
+            buf: Vec::new(),
+            head: 0,
+            scanned: 0,
+            encoding,
+        })
+    }
+
+    fn next_line(&mut self) -> io::Result<Option<(u64, Vec<u8>)>> {
+        loop {
+            let from = self.scanned.max(self.head);
+            let base = self.pos + (from - self.head) as u64;
+            if let Some(i) = self.encoding.find_newline(&self.buf[from..], base) {
+                let stop = from + i;
+                let line = self.buf[self.head..stop].to_vec();
+                let offset = self.pos;
+
+                let next = stop + self.encoding.newline_len();
+                self.pos += (next - self.head) as u64;
+                self.head = next;
+                return Ok(Some((offset, line)));
+            }
+
+            let read = self.pos + (self.buf.len() - self.head) as u64;
+            if read >= self.end {
+                if self.head == self.buf.len() {
+                    return Ok(None);
+                }
+                let line = self.buf.split_off(self.head);
+                let offset = self.pos;
+                self.pos += line.len() as u64;
+                // Nothing is left to scan, so the next call ends the walk.
+                self.buf.clear();
+                self.head = 0;
+                self.scanned = 0;
+                return Ok(Some((offset, line)));
+            }
+
+            // A newline's first byte may be the last one read, so it's scanned again.
+            self.scanned = self
+                .buf
+                .len()
+                .saturating_sub(self.encoding.newline_len() - 1);
+
+            // Move the unfinished line to the front and read the next block after it.
+            self.buf.drain(..self.head);
+            self.scanned -= self.head.min(self.scanned);
+            self.head = 0;
+
+            let len = self.buf.len();
+            let want = (self.end - read).min(BLOCK_SIZE) as usize;
+            self.buf.resize(len + want, 0);
+            self.f.read_exact(&mut self.buf[len..])?;
+        }
+    }
+}
+
+impl Iterator for FwdLines<'_> {
+    type Item = io::Result<(u64, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_line().transpose()
+    }
+}
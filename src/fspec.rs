@@ -0,0 +1,256 @@
+use std::{
+    fs::{File, Metadata},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use crate::compress::Compression;
+use crate::decode::Encoding;
+use crate::error::{Error, Result};
+use crate::reverse::RevLines;
+
+#[cfg(target_os = "windows")]
+use filesize::PathExt;
+
+/// Identity of the file behind a path, so a rotated-in replacement can be told apart from
+/// the file we have open. Only available on unix, where it is the (device, inode) pair.
+type FileId = (u64, u64);
+
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> Option<FileId> {
+    None
+}
+
+/// What the path of a followed file currently points at, relative to the open handle.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PathState {
+    Same,
+    Missing,
+    Replaced,
+}
+
+pub struct FileSpec {
+    pub size: u64,
+    pub fpath: Option<PathBuf>,
+    // Name used in headers and prefixes, as given on the command line.
+    pub name: String,
+    // Canonical path, to match watcher events back to this file.
+    pub canon: Option<PathBuf>,
+    // Kept open so a renamed file can still be drained after rotation.
+    handle: Option<File>,
+    id: Option<FileId>,
+    // Trailing bytes read without a newline yet, and when they started waiting.
+    partial: Vec<u8>,
+    pub partial_since: Option<Instant>,
+    pub encoding: Encoding,
+    // Compressed files can only be read through from the start, and aren't followed.
+    pub compressed: bool,
+    // Older, rotated generations of the file, oldest first, for --include-rotated.
+    pub rotated: Vec<PathBuf>,
+}
+
+impl FileSpec {
+    pub fn new(fpath: Option<PathBuf>, encoding: Encoding) -> Self {
+        let size: u64 = 0;
+        let name = fpath
+            .as_ref()
+            .map_or_else(|| "standard input".to_string(), |p| p.display().to_string());
+        let canon = fpath.as_deref().and_then(canonical);
+        let mut ret = Self {
+            fpath,
+            size,
+            name,
+            canon,
+            handle: None,
+            id: None,
+            partial: Vec::new(),
+            partial_since: None,
+            encoding,
+            compressed: false,
+            rotated: Vec::new(),
+        };
+        ret.open();
+        ret.update_size();
+        ret
+    }
+
+    /// (Re)open the file behind the path. Returns false if it can't be opened (yet).
+    pub fn open(&mut self) -> bool {
+        let Some(fpath) = &self.fpath else {
+            return false;
+        };
+
+        match File::open(fpath) {
+            Ok(mut f) => {
+                self.id = f.metadata().ok().as_ref().and_then(file_id);
+                self.compressed = matches!(Compression::detect(&mut f), Ok(Some(_)));
+                self.handle = Some(f);
+                true
+            }
+            Err(_) => {
+                self.handle = None;
+                self.id = None;
+                false
+            }
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    // size_on_disk() wasn't returning actual file size for linux.
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    pub fn update_size(&mut self) {
+        self.size = self.open_len();
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn update_size(&mut self) {
+        self.size = match &self.fpath {
+            Some(fpath) if self.handle.is_some() => fpath.size_on_disk().unwrap_or(0),
+            _ => 0,
+        };
+    }
+
+    /// Current length of the open file, which may no longer be the one at the path.
+    pub fn open_len(&self) -> u64 {
+        self.handle
+            .as_ref()
+            .and_then(|f| f.metadata().ok())
+            .map_or(0, |m| m.len())
+    }
+
+    /// Read everything appended since the last read and advance past it, decoded line by
+    /// line. Only whole lines are returned; a trailing fragment is held back until the rest
+    /// of it is written.
+    pub fn read_lines(&mut self) -> Result<Vec<Vec<u8>>> {
+        let mut buf = Vec::new();
+        if let Some(f) = &mut self.handle {
+            f.seek(SeekFrom::Start(self.size))
+                .and_then(|_| f.read_to_end(&mut buf))
+                .map_err(|e| Error::read(&self.name, e))?;
+        }
+
+        Ok(self.push(&buf))
+    }
+
+    /// Take in bytes that follow what has been read so far, e.g. a chunk of piped input, and
+    /// return the lines they complete, decoded. A trailing fragment is held back as above.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Vec<u8>> {
+        let base = self.complete_len();
+        let mut buf = std::mem::take(&mut self.partial);
+        buf.extend_from_slice(chunk);
+        self.size += chunk.len() as u64;
+
+        let complete = self
+            .encoding
+            .rfind_newline(&buf, base)
+            .map_or(0, |i| i + self.encoding.newline_len());
+        self.partial = buf.split_off(complete);
+
+        if self.partial.is_empty() {
+            self.partial_since = None;
+        } else if complete > 0 || self.partial_since.is_none() {
+            self.partial_since = Some(Instant::now());
+        }
+
+        self.encoding
+            .split_lines(&buf, base)
+            .into_iter()
+            .map(|line| self.encoding.decode(line).into_owned())
+            .collect()
+    }
+
+    /// Move an unfinished last line into the partial buffer, so it's only seen once whole.
+    pub fn hold_partial(&mut self) -> Result<()> {
+        let partial = self
+            .unterminated_line()
+            .map_err(|e| Error::read(&self.name, e))?;
+
+        if let Some(partial) = partial {
+            self.partial = partial;
+            self.partial_since = Some(Instant::now());
+        }
+        Ok(())
+    }
+
+    // The last line of the file if no newline ends it.
+    fn unterminated_line(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let Some(f) = &mut self.handle else {
+            return Ok(None);
+        };
+        if self.size == 0 {
+            return Ok(None);
+        }
+
+        let nl = self.encoding.newline_len() as u64;
+        let mut last = vec![0; nl.min(self.size) as usize];
+        let at = self.size - last.len() as u64;
+        f.seek(SeekFrom::Start(at))?;
+        f.read_exact(&mut last)?;
+
+        if self.encoding.rfind_newline(&last, at) == Some(0) && last.len() as u64 == nl {
+            return Ok(None);
+        }
+        let line = RevLines::new(f, self.size, self.encoding)?
+            .next()
+            .transpose()?;
+        Ok(line.map(|(_, line)| line))
+    }
+
+    /// Hand back whatever is waiting for a newline, decoded, e.g. once a flush timeout expires.
+    pub fn take_partial(&mut self) -> Vec<u8> {
+        self.partial_since = None;
+        let partial = std::mem::take(&mut self.partial);
+        self.encoding.decode(&partial).into_owned()
+    }
+
+    /// Offset up to which the file holds whole lines.
+    pub fn complete_len(&self) -> u64 {
+        self.size - self.partial.len() as u64
+    }
+
+    pub fn path_state(&self) -> PathState {
+        let Some(meta) = self.fpath.as_ref().and_then(|p| p.metadata().ok()) else {
+            return PathState::Missing;
+        };
+
+        match (self.id, file_id(&meta)) {
+            _ if self.handle.is_none() => PathState::Replaced,
+            (Some(old), Some(new)) if old != new => PathState::Replaced,
+            _ => PathState::Same,
+        }
+    }
+
+    pub fn is_path(&self, path: &Path) -> bool {
+        self.canon.as_deref() == Some(path) || self.fpath.as_deref() == Some(path)
+    }
+}
+
+/// Canonical form of a path that may not exist yet: its parent is resolved instead.
+fn canonical(path: &Path) -> Option<PathBuf> {
+    path.canonicalize().ok().or_else(|| {
+        Some(
+            parent_dir(path)
+                .canonicalize()
+                .ok()?
+                .join(path.file_name()?),
+        )
+    })
+}
+
+/// The directory a file lives in, which is watched when following by name.
+pub fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
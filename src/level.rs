@@ -0,0 +1,70 @@
+use std::sync::LazyLock;
+
+use clap::ValueEnum;
+use colored::Color;
+use regex::bytes::Regex;
+
+// Where a level is written in plain text: an upper case word like `ERROR`, a bracketed word
+// like `[warn]`, a `level=` field, or a JSON `"level":` field.
+static PATTERN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"\b(TRACE|DEBUG|INFO|NOTICE|WARN|WARNING|ERROR|ERR|CRIT|CRITICAL|FATAL|ALERT|EMERG|PANIC)\b|\[(?i:(trace|debug|info|notice|warn|warning|error|err|crit|critical|fatal))\]|\b(?i:level|lvl|severity)=(\w+)|"(?i:level|lvl|severity)"\s*:\s*"?(\w+)"#,
+    )
+    .unwrap()
+});
+
+/// How severe a line is, from least to most.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl Level {
+    /// A level from its name, in any case, or from a number: syslog's priorities 0 to 7, or
+    /// the 10 to 60 used by pino and bunyan.
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "trace" | "10" => Level::Trace,
+            "debug" | "7" | "20" => Level::Debug,
+            "info" | "notice" | "5" | "6" | "30" => Level::Info,
+            "warn" | "warning" | "4" | "40" => Level::Warn,
+            "error" | "err" | "3" | "50" => Level::Error,
+            "fatal" | "critical" | "crit" | "alert" | "emerg" | "panic" | "0" | "1" | "2"
+            | "60" => Level::Fatal,
+            _ => return None,
+        })
+    }
+
+    /// The first level named in a line of plain text.
+    pub fn detect(line: &[u8]) -> Option<Self> {
+        let caps = PATTERN.captures(line)?;
+        let word = caps.iter().skip(1).flatten().next()?;
+        Level::parse(std::str::from_utf8(word.as_bytes()).ok()?)
+    }
+
+    /// The colour of the level's name.
+    pub fn color(self) -> Color {
+        match self {
+            Level::Fatal | Level::Error => Color::Red,
+            Level::Warn => Color::Yellow,
+            Level::Info => Color::Green,
+            Level::Debug | Level::Trace => Color::Blue,
+        }
+    }
+
+    /// The colour of a whole line at this level. Info lines are left alone, and debug and
+    /// trace lines are greyed out.
+    pub fn line_color(self) -> Option<Color> {
+        match self {
+            Level::Fatal | Level::Error => Some(Color::Red),
+            Level::Warn => Some(Color::Yellow),
+            Level::Info => None,
+            Level::Debug | Level::Trace => Some(Color::BrightBlack),
+        }
+    }
+}
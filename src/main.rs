@@ -1,196 +1,858 @@
 use std::{
     fs::File,
-    io::{IsTerminal, Read, Seek, SeekFrom},
-    path::Path,
-    str::from_utf8,
+    io::{self, IsTerminal, Read},
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
-use clap::{arg, command, Parser};
-use colored::Colorize;
-use notify::{RecursiveMode, Watcher};
+use chrono::{DateTime, Utc};
+use clap::{error::ErrorKind, CommandFactory, Parser};
+
+mod compress;
+mod context;
+mod count;
+mod decode;
+mod error;
+mod fields;
+mod follow;
+mod forward;
+mod fspec;
+mod level;
+mod merge;
+mod pager;
+mod printer;
+mod records;
+mod reverse;
+mod sieve;
+mod tail;
+mod timestamp;
+#[cfg(feature = "tui")]
+mod tui;
+
+use context::Context;
+use count::{Count, Unit};
+use decode::{Binary, Encoding};
+use error::Error;
+use fields::{Fields, Format, Predicate};
+use follow::FollowMode;
+use forward::FwdLines;
+use fspec::FileSpec;
+use level::Level;
+use merge::Merge;
+use pager::Pager;
+use printer::{Filters, Label, Line, Printer};
+use records::{Pending, Preset, Records};
+use reverse::RevLines;
+use sieve::{Combinator, Sieve};
+use tail::Tail;
+use timestamp::{Span, Stamps};
+
+/// Expand glob patterns among the given paths. Paths that exist are taken literally, so
+/// file names containing glob characters still work. With `retry`, paths that match nothing
+/// are kept so they can be picked up once they are created.
+fn expand_paths(files: &[String], retry: bool) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    for file in files {
+        let path = PathBuf::from(file);
+        if path.exists() {
+            paths.push(path);
+            continue;
+        }
+
+        let matched: Vec<PathBuf> = glob::glob(file)
+            .map(|entries| entries.filter_map(Result::ok).collect())
+            .unwrap_or_default();
 
-use static_str::to_str;
+        if matched.is_empty() {
+            eprintln!("trunk: cannot open '{}': No such file or directory", file);
+            if retry {
+                paths.push(path);
+            }
+        }
+        paths.extend(matched);
+    }
 
-struct FileSpec {
-    size: u64,
-    fpath: Option<&'static Path>,
-    stdin: Option<Vec<String>>,
+    paths
 }
 
-impl FileSpec {
-    fn new(fpath: Option<&'static Path>, stdin: Option<Vec<String>>) -> Self {
-        let size: u64 = 0;
-        let mut ret = Self { fpath, size, stdin };
-        ret.update_size();
-        ret
+fn main() {
+    let mut args = Args::parse();
+
+    // -F is shorthand for --follow=name --retry
+    if args.follow_name {
+        args.follow = Some(FollowMode::Name);
+        args.retry = true;
     }
 
-    // size_on_disk() wasn't returning actual file size for linux.
-    #[cfg(any(target_os = "linux", target_os = "macos"))]
-    fn update_size(&mut self) {
-        self.size = if self.fpath.is_some() {
-            self.fpath.unwrap().metadata().unwrap().len()
-        } else {
-            0
+    // The TUI takes over the terminal, and it follows
+    #[cfg(feature = "tui")]
+    if args.tui {
+        if !io::stdout().is_terminal() {
+            Args::command()
+                .error(ErrorKind::ArgumentConflict, "--tui needs a terminal")
+                .exit()
         }
-        // self.size = self.fpath.unwrap().metadata().unwrap().len();
+        args.follow.get_or_insert(FollowMode::Descriptor);
     }
 
-    #[cfg(target_os = "windows")]
-    fn update_size(&mut self) {
-        self.size = self.fpath.unwrap().size_on_disk().unwrap();
+    // Paging is done while following
+    if args.interactive {
+        if !io::stdout().is_terminal() {
+            Args::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "--interactive needs a terminal",
+                )
+                .exit()
+        }
+        args.follow.get_or_insert(FollowMode::Descriptor);
     }
-}
 
-fn main() {
-    let mut args = Args::parse();
+    // Polling only makes sense when following
+    if args.poll.is_some() {
+        args.follow.get_or_insert(FollowMode::Descriptor);
+    }
 
-    let filepath = to_str(args.file);
+    let paths = expand_paths(&args.file, args.retry);
+    if !args.file.is_empty() && paths.is_empty() {
+        std::process::exit(1);
+    }
 
-    let path = if filepath != "" {
-        Some(Path::new(filepath))
+    let combinator = if args.all {
+        Combinator::All
     } else {
-        None
+        Combinator::Any
     };
 
-    let input = std::io::stdin();
-
-    let stdin_lines: Option<Vec<String>> = if !input.is_terminal() {
-        Some(input.lines().collect::<Result<Vec<_>, _>>().unwrap())
+    // Automatically follow if sieve is specified
+    let sieve = if !args.sieve.is_empty() || !args.exclude.is_empty() {
+        args.follow.get_or_insert(FollowMode::Descriptor);
+
+        match Sieve::new(
+            &args.sieve,
+            &args.exclude,
+            args.regex,
+            args.groups,
+            combinator,
+        ) {
+            Ok(sieve) => Some(sieve),
+            Err(e) => Args::command()
+                .error(ErrorKind::ValueValidation, format!("invalid sieve: {}", e))
+                .exit(),
+        }
     } else {
         None
     };
 
-    let sieve = to_str(args.sieve);
+    let records = match (&args.record_start, args.records) {
+        (Some(start), _) => match Records::new(start) {
+            Ok(records) => Some(records),
+            Err(e) => Args::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!("invalid record start: {}", e),
+                )
+                .exit(),
+        },
+        (None, Some(preset)) => Some(Records::preset(preset)),
+        (None, None) => None,
+    };
 
-    // Automatically follow if sieve is specified
-    if sieve != "" {
-        args.follow = true;
+    // The TUI sieves lines itself, so that the sieve can be changed, and is given all of them.
+    #[cfg(feature = "tui")]
+    let sieve = sieve.filter(|_| !args.tui);
+
+    // Piped input is followed as a stream, so it isn't read here.
+    let follow_stdin = paths.is_empty() && args.follow.is_some() && !std::io::stdin().is_terminal();
+
+    // Otherwise it is read to its end for the backlog.
+    let read_stdin = paths.is_empty() && !follow_stdin && !std::io::stdin().is_terminal();
+
+    let mut specs: Vec<FileSpec> = if paths.is_empty() {
+        vec![FileSpec::new(None, args.encoding)]
+    } else {
+        paths
+            .into_iter()
+            .map(|p| FileSpec::new(Some(p), args.encoding))
+            .collect()
+    };
+    if args.include_rotated {
+        for spec in &mut specs {
+            spec.rotated = spec
+                .fpath
+                .as_deref()
+                .map(compress::rotated)
+                .unwrap_or_default();
+        }
     }
 
-    let mut fspec = FileSpec::new(path, stdin_lines);
+    // Merged lines could come from any file, so each says which.
+    let label = if args.prefix || (args.merge && specs.len() > 1 && !args.quiet) {
+        Label::Prefix
+    } else if specs.len() > 1 && !args.quiet {
+        Label::Header
+    } else {
+        Label::None
+    };
+    let names = specs.iter().map(|s| s.name.clone()).collect();
+    let context = Context {
+        before: args.before_context.or(args.context).unwrap_or(0),
+        after: args.after_context.or(args.context).unwrap_or(0),
+    };
+    let format = if args.json {
+        Some(Format::Json)
+    } else if args.logfmt {
+        Some(Format::Logfmt)
+    } else {
+        args.format
+    };
+    let fields = format.map(|f| Fields::new(f, args.filter.clone(), args.fields.clone()));
+    let has_span = args.since.is_some() || args.until.is_some();
+    let stamps = Stamps::new(args.ts_format.clone());
+    let span = has_span.then(|| Span::new(args.since, args.until, stamps.clone()));
+    let filters = Filters {
+        fields,
+        min_level: args.min_level,
+        span,
+    };
+    let merge = args
+        .merge
+        .then(|| Merge::new(stamps, args.merge_window, specs.len()));
+    let mut printer = Printer::new(label, names, args.binary, context, records, filters, merge);
+    if args.interactive {
+        printer.page(Pager::new(args.regex));
+    }
+    #[cfg(feature = "tui")]
+    let lines = args.tui.then(|| {
+        // The TUI colours lines itself.
+        colored::control::set_override(false);
+        let (tx, rx) = std::sync::mpsc::channel();
+        printer.capture(tx);
+        rx
+    });
+
+    // A time span takes the place of -n: all of it is printed.
+    let (unit, count) = match args.bytes {
+        _ if has_span => (Unit::Lines, Count::From(1)),
+        Some(bytes) => (Unit::Bytes, bytes),
+        None => (Unit::Lines, args.num_lines),
+    };
 
-    let num = args.num_lines.parse::<i32>().unwrap();
-    read_last_n_lines(&mut fspec, num);
+    let following =
+        args.follow.is_some() && (follow_stdin || specs.iter().any(|s| s.fpath.is_some()));
+
+    // A file that can't be read is reported and skipped, and the exit status says so.
+    let mut failed = false;
+    for (idx, fspec) in specs.iter_mut().enumerate() {
+        // An unfinished last line is left for follow mode to complete.
+        let held = match args.follow {
+            Some(_) if !fspec.compressed => fspec.hold_partial(),
+            _ => Ok(()),
+        };
+
+        printer.header(idx);
+        let res = held.and_then(|_| {
+            if fspec.fpath.is_some() {
+                read_backlog(
+                    idx,
+                    fspec,
+                    &mut printer,
+                    unit,
+                    count,
+                    context,
+                    sieve.as_ref(),
+                )
+            } else if read_stdin {
+                tail_stdin(
+                    idx,
+                    fspec,
+                    &mut printer,
+                    unit,
+                    count,
+                    context,
+                    sieve.as_ref(),
+                )
+            } else {
+                Ok(())
+            }
+        });
 
-    if args.follow && path.is_some() {
-        let mut watcher = notify::recommended_watcher(move |res| match res {
-            Ok(_event) => follow_filter(&mut fspec, sieve),
-            Err(e) => println!("watch error: {:?}", e),
-        })
-        .unwrap();
+        // Unless it's followed, the file's last record is complete.
+        if !following {
+            printer.finish_records(true, sieve.as_ref());
+        }
 
-        watcher
-            .watch(path.unwrap(), RecursiveMode::Recursive)
-            .unwrap();
-        loop {}
+        if let Err(e) = res {
+            printer.flush();
+            eprintln!("trunk: {}", e);
+            failed = true;
+        }
+    }
+    printer.flush_all();
+
+    let follow = args
+        .follow
+        .filter(|_| following)
+        .map(|mode| follow::Options {
+            mode,
+            poll: args.poll,
+            partial_timeout: args.partial_timeout,
+            stdin: follow_stdin,
+            unit,
+            count,
+            context,
+        });
+
+    #[cfg(feature = "tui")]
+    if let Some(lines) = lines {
+        let options = tui::Options {
+            names: specs.iter().map(|s| s.name.clone()).collect(),
+            paths: specs.iter().map(|s| s.fpath.clone()).collect(),
+            sieve: args.sieve,
+            exclude: args.exclude,
+            regex: args.regex,
+            groups: args.groups,
+            combinator,
+        };
+        // Once the printer is dropped, the TUI knows no more lines are coming.
+        let follow = follow.map(|options| {
+            move || follow::run(&mut specs, &mut printer, None, &options).interrupted()
+        });
+        if let Err(e) = tui::run(options, lines, follow) {
+            eprintln!("trunk: cannot run the TUI: {}", e);
+            std::process::exit(1);
+        }
+        std::process::exit(i32::from(failed));
     }
-}
 
-fn read_last_n_lines(file: &mut FileSpec, num: i32) {
-    let mut b_ns = num;
-    let mut start: u64 = 0;
+    if let Some(options) = follow {
+        let summary = follow::run(&mut specs, &mut printer, sieve.as_ref(), &options);
+        eprintln!("{}", summary);
+    }
 
-    if file.fpath.is_some() {
-        let mut f = File::options()
-            .read(true)
-            .write(false)
-            .open(file.fpath.unwrap())
-            .unwrap();
+    if failed {
+        std::process::exit(1);
+    }
+}
 
-        // Stop when number of \n are met, or the file is completely read.
-        while b_ns > 0 && start < file.size {
-            // Read one byte at a time until we reach the specified number of \n's (b_ns)
-            start = start + 1;
+/// Print the part of the file given by -n or -c. Only the blocks that part spans are read.
+/// With --include-rotated, it's measured across the file's older generations and the file
+/// together, with `+N` offsets counted from the start of the oldest.
+fn read_backlog(
+    idx: usize,
+    file: &mut FileSpec,
+    printer: &mut Printer,
+    unit: Unit,
+    count: Count,
+    context: Context,
+    sieve: Option<&Sieve>,
+) -> error::Result<()> {
+    let Some(fpath) = file.fpath.as_ref().filter(|_| file.is_open()) else {
+        // Only possible with --retry; there is no backlog until the file appears.
+        return Ok(());
+    };
 
-            f.seek(SeekFrom::Start(file.size - start)).unwrap();
+    // A compressed file can't be read backwards, so it's read through like piped input.
+    if file.compressed {
+        let mut tail = Tail::new(unit, count, context);
+        let res = tail_file(idx, fpath, file.encoding, printer, &mut tail, sieve);
+        emit_tail(idx, printer, tail, sieve);
+        return res;
+    }
 
-            let mut buf = vec![0; 1];
-            f.read_exact(&mut buf).unwrap();
+    let read = |e| Error::read(&file.name, e);
+    let mut f = File::options()
+        .read(true)
+        .write(false)
+        .open(fpath)
+        .map_err(|e| Error::open(&file.name, e))?;
+    let mut end = file.complete_len();
+
+    // Besides where to start, how much of the rotated generations is printed ahead of it.
+    let (start, skip, older) = if let Some(span) = printer.span() {
+        // Only the part of the file within the span is read, found by bisecting it.
+        let (start, stop) = span.seek(&mut f, end, file.encoding).map_err(read)?;
+        end = stop;
+        (start, 0, Some(Count::From(1)))
+    } else {
+        match (unit, count) {
+            (Unit::Lines, Count::Last(num)) => {
+                // Walk back until enough lines make it through, and then over the context
+                // before the first of them; nothing before that is read. They're printed
+                // going forwards. With records, it's whole records that are counted.
+                let mut start = end;
+                let mut found = 0;
+                let mut leading = 0;
+                let mut record = Vec::new();
+                // Where the earliest line read so far starts.
+                let mut first = end;
+                let lines = RevLines::new(&mut f, end, file.encoding).map_err(read)?;
+                // Reaching the start of the file ends the lines before the first record
+                // start, which make up a record of their own.
+                for line in lines.map(Some).chain([None]) {
+                    let offset = match line {
+                        Some(line) => {
+                            let (offset, line) = line.map_err(read)?;
+                            let line = file.encoding.decode(&line).into_owned();
+                            let starts = printer.starts_record(&line);
+                            record.push(line);
+                            first = offset;
+                            if !starts {
+                                continue;
+                            }
+                            offset
+                        }
+                        None if !record.is_empty() => first,
+                        None => break,
+                    };
+
+                    record.reverse();
+                    let passes = printer.passes(&Line::new(record.join(&b'\n')), sieve);
+                    record.clear();
+
+                    if passes && found < num {
+                        found += 1;
+                        leading = 0;
+                    } else if found > 0 && !passes && leading < context.before {
+                        leading += 1;
+                    } else if found == num {
+                        break;
+                    } else {
+                        continue;
+                    }
+                    start = offset;
+                }
+                (start, 0, (found < num).then(|| Count::Last(num - found)))
+            }
+            (Unit::Lines, Count::From(num)) => (0, num.saturating_sub(1), Some(count)),
+            (Unit::Bytes, Count::Last(num)) => (
+                end.saturating_sub(num),
+                0,
+                (end < num).then(|| Count::Last(num - end)),
+            ),
+            (Unit::Bytes, Count::From(num)) => (num.saturating_sub(1).min(end), 0, Some(count)),
+        }
+    };
 
-            if from_utf8(&buf).unwrap() == "\n".to_string() {
-                b_ns -= 1;
+    let (mut start, mut skip) = (start, skip);
+    if let Some(count) = older.filter(|_| !file.rotated.is_empty()) {
+        // A time span is found line by line, whatever -c says.
+        let unit = if printer.span().is_some() {
+            Unit::Lines
+        } else {
+            unit
+        };
+        let seen = read_rotated(idx, file, printer, unit, count, context, sieve)?;
+
+        // What of a `+N` offset the older generations didn't use up is left for the file.
+        match (unit, count) {
+            (Unit::Lines, Count::From(num)) => skip = num.saturating_sub(1).saturating_sub(seen),
+            (Unit::Bytes, Count::From(num)) => {
+                start = num.saturating_sub(1).saturating_sub(seen).min(end)
             }
+            _ => {}
         }
+    }
 
-        // Seek to position of last \n and print rest of the file out.
-        f.seek(SeekFrom::Start(file.size - start)).unwrap();
+    let start = file.encoding.align(start).min(end);
+    let lines = FwdLines::new(&mut f, start, end, file.encoding).map_err(read)?;
+    for line in lines.skip(skip as usize) {
+        let (_, line) = line.map_err(read)?;
+        printer.emit(idx, &file.encoding.decode(&line), sieve);
+    }
+    Ok(())
+}
 
-        let mut buf_print = Vec::new();
-        f.read_to_end(&mut buf_print).unwrap();
+/// Read piped input to its end, printing the part of it given by -n or -c. Only as much
+/// of it as that needs is held in memory.
+fn tail_stdin(
+    idx: usize,
+    file: &FileSpec,
+    printer: &mut Printer,
+    unit: Unit,
+    count: Count,
+    context: Context,
+    sieve: Option<&Sieve>,
+) -> error::Result<()> {
+    let mut tail = Tail::new(unit, count, context);
+    let mut input = io::stdin().lock();
+    let res = tail_stream(
+        idx,
+        &mut input,
+        &file.name,
+        file.encoding,
+        printer,
+        &mut tail,
+        sieve,
+    );
+    emit_tail(idx, printer, tail, sieve);
+    res
+}
 
-        print!("{}", String::from_utf8(buf_print).unwrap());
-    } else if file.stdin.is_some() {
-        // Far easier to do when input is stdin string...
+/// Print the end of a file's rotated generations, oldest first, measured as `count` across
+/// all of them. With a time span, only the generations it reaches back into are read.
+/// Returns how many lines or bytes were read, for a `+N` offset to carry on counting from.
+fn read_rotated(
+    idx: usize,
+    file: &FileSpec,
+    printer: &mut Printer,
+    unit: Unit,
+    count: Count,
+    context: Context,
+    sieve: Option<&Sieve>,
+) -> error::Result<u64> {
+    let mut paths = file.rotated.as_slice();
+    if let Some(span) = printer.span() {
+        // Anything older than the newest file that starts before the span is outside it.
+        let starts_before = |path: &PathBuf| {
+            let mut head = Vec::new();
+            let read = compress::open(path).and_then(|f| f.take(64 * 1024).read_to_end(&mut head));
+            read.is_ok()
+                && file
+                    .encoding
+                    .split_lines(&head, 0)
+                    .into_iter()
+                    .find_map(|line| span.is_before(&file.encoding.decode(line)))
+                    .unwrap_or(false)
+        };
+        if file.fpath.as_ref().is_some_and(starts_before) {
+            return Ok(0);
+        }
+        let oldest = paths.iter().rposition(starts_before).unwrap_or(0);
+        paths = &paths[oldest..];
+    }
 
-        let mut buf_print = String::new();
+    let mut tail = Tail::new(unit, count, context);
+    let res = paths
+        .iter()
+        .try_for_each(|path| tail_file(idx, path, file.encoding, printer, &mut tail, sieve));
+    let seen = tail.seen();
+    emit_tail(idx, printer, tail, sieve);
+    res.map(|_| seen)
+}
 
-        for lines in file.stdin.clone().unwrap().iter().rev() {
-            buf_print.insert_str(0, &format!("{}\n", lines)[..]);
-            b_ns -= 1;
-            if b_ns == 0 {
+/// Read a file through from the start into `tail`, decompressing it if need be.
+fn tail_file(
+    idx: usize,
+    path: &Path,
+    encoding: Encoding,
+    printer: &mut Printer,
+    tail: &mut Tail,
+    sieve: Option<&Sieve>,
+) -> error::Result<()> {
+    let name = path.display().to_string();
+    let mut input = compress::open(path).map_err(|e| Error::open(&name, e))?;
+    tail_stream(idx, &mut input, &name, encoding, printer, tail, sieve)
+}
+
+/// Read input to its end into `tail`, which hands back the lines past a `+N` offset to be
+/// printed straight away and keeps the last N for `emit_tail`.
+fn tail_stream(
+    idx: usize,
+    input: &mut dyn Read,
+    name: &str,
+    encoding: Encoding,
+    printer: &mut Printer,
+    tail: &mut Tail,
+    sieve: Option<&Sieve>,
+) -> error::Result<()> {
+    // Lines are grouped into records, if asked for, before they're counted.
+    let mut lines = FileSpec::new(None, encoding);
+    let mut pending = Pending::default();
+    // `unterminated` is whether the line, or the end of input, comes without a newline.
+    let mut keep = |line: Option<Vec<u8>>, unterminated: bool| {
+        let record = match line {
+            Some(line) => printer.gather(&mut pending, line),
+            // The end of input completes the last record.
+            None => pending.take(),
+        };
+        let Some(record) = record else { return };
+
+        // A record handed back while another is still gathered ended with a newline.
+        let record = Line::new(record);
+        let passes = printer.passes(&record, sieve);
+        let printed = if unterminated && pending.since.is_none() {
+            tail.push_last(record, passes)
+        } else {
+            tail.push(record, passes)
+        };
+        if let Some(record) = printed {
+            printer.emit_held(idx, record, sieve);
+        }
+    };
+
+    // What was read before an error is still printed.
+    let mut res = Ok(());
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => lines
+                .push(&buf[..n])
+                .into_iter()
+                .for_each(|line| keep(Some(line), false)),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => {
+                res = Err(Error::read(name, e));
                 break;
             }
-
-            print!("{}", buf_print);
         }
     }
+
+    // The input may not end with a newline.
+    let partial = lines.take_partial();
+    let unterminated = !partial.is_empty();
+    if unterminated {
+        keep(Some(partial), true);
+    }
+    keep(None, unterminated);
+    res
 }
 
-fn follow_filter(file: &mut FileSpec, filter: &str) {
-    if file.fpath.unwrap().metadata().unwrap().len() >= file.size {
-        // Regular tail -f behaviour so far.
-        let mut f = File::options()
-            .read(true)
-            .write(false)
-            .open(file.fpath.unwrap())
-            .unwrap();
-
-        // delay updating file.size so that we know exact seek of where the last output ended
-        f.seek(SeekFrom::Start(file.size)).unwrap();
-
-        let mut buf = Vec::new();
-        f.read_to_end(&mut buf).unwrap();
-        let new_line = String::from_utf8(buf).unwrap();
-
-        // Start filtering things out here...
-        let lines = new_line.split("\n");
-
-        for line in lines {
-            if line.contains(&filter) {
-                let phrases: Vec<&str> = line.split(filter).collect();
-                for i in phrases[..phrases.len() - 1].iter() {
-                    print!("{}", *i);
-                    print!("{}", filter.red());
-                }
-                println!("{}", phrases[phrases.len() - 1]);
-            }
+/// Print what `tail` kept.
+fn emit_tail(idx: usize, printer: &mut Printer, tail: Tail, sieve: Option<&Sieve>) {
+    for line in tail {
+        match line {
+            Some(line) => printer.emit_held(idx, line, sieve),
+            None => printer.skip(idx),
         }
-    } else {
-        // Display message informing that the file size reduced since the last output. continue following new EOF
-        println!("***FILE TRUNCATED: READING FROM NEW EOF***");
     }
+}
 
-    // update file.size after we are done printing/filtering
-    file.update_size();
+/// A number of seconds, which may be fractional.
+fn parse_secs(s: &str) -> Result<Duration, String> {
+    let secs: f64 = s
+        .parse()
+        .map_err(|_| "not a number of seconds".to_string())?;
+    Duration::try_from_secs_f64(secs)
+        .map_err(|_| "must be a positive number of seconds".to_string())
+}
+
+/// A number of seconds to wait between checks, which can't be zero.
+fn parse_interval(s: &str) -> Result<Duration, String> {
+    match parse_secs(s)? {
+        secs if secs.is_zero() => Err("must be more than 0".to_string()),
+        secs => Ok(secs),
+    }
 }
 
 #[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
 struct Args {
     /// Follow a file for live changes.
+    #[arg(
+        short,
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "descriptor"
+    )]
+    follow: Option<FollowMode>,
+
+    /// Same as --follow=name --retry
+    #[arg(short = 'F', action)]
+    follow_name: bool,
+
+    /// Keep trying to open a file that is missing or becomes inaccessible.
+    #[arg(long, action)]
+    retry: bool,
+
+    /// Poll for changes every SECS seconds instead of waiting on filesystem events. Used
+    /// automatically on network filesystems or where events aren't available. Implies [-f --follow]
+    #[arg(
+        long,
+        value_name = "SECS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "1",
+        value_parser = parse_interval
+    )]
+    poll: Option<Duration>,
+
+    /// Print an unfinished line once it has waited SECS seconds for its newline, instead of
+    /// holding it until the line is complete.
+    #[arg(long, value_name = "SECS", value_parser = parse_secs)]
+    partial_timeout: Option<Duration>,
+
+    /// Phrase to filter new lines with. Repeat to give several phrases. Will automatically enable [-f --follow]
+    #[arg(short, long)]
+    sieve: Vec<String>,
+
+    /// Phrase to drop lines with, even if they match a sieve. Can be repeated.
+    #[arg(short = 'v', long)]
+    exclude: Vec<String>,
+
+    /// Only keep lines that match every sieve.
+    #[arg(long, action, conflicts_with = "any")]
+    all: bool,
+
+    /// Keep lines that match any sieve. This is the default.
+    #[arg(long, action)]
+    any: bool,
+
+    /// Treat the sieve as a regular expression instead of a literal phrase.
+    #[arg(short = 'E', long, action)]
+    regex: bool,
+
+    /// Highlight each capture group of a regex sieve in its own colour.
+    #[arg(long, action, requires = "regex")]
+    groups: bool,
+
+    /// Treat lines as JSON objects: filter them with --where and print them compactly.
+    /// Lines that aren't JSON are printed as they are.
+    #[arg(long, action, group = "structured")]
+    json: bool,
+
+    /// Treat lines as logfmt `key=value` pairs, like --json. Lines that aren't logfmt are
+    /// printed as they are.
+    #[arg(long, action, group = "structured")]
+    logfmt: bool,
+
+    /// Parse lines in a known log format so --where and --fields can name their fields.
+    /// Fixed layouts like syslog and access logs are printed in columns.
+    #[arg(long, value_enum, group = "structured")]
+    format: Option<Format>,
+
+    /// With --json, --logfmt or --format, only keep lines whose FIELD compares to VALUE, e.g.
+    /// `level=error` or `status>=500`. Operators are = != > >= < <= and ~ for a regex. Nested
+    /// fields are named with dots. Can be repeated; every condition must hold.
+    #[arg(long = "where", value_name = "COND", requires = "structured")]
+    filter: Vec<Predicate>,
+
+    /// Comma separated fields to print, in order.
+    #[arg(
+        long,
+        value_name = "FIELDS",
+        value_delimiter = ',',
+        requires = "structured"
+    )]
+    fields: Vec<String>,
+
+    /// Drop lines below this level. Levels are read from words like ERROR or [warn], fields
+    /// like level=warn or "level":"warn", or the level field with --json, --logfmt or
+    /// --format. Lines without one are kept.
+    #[arg(long, value_name = "LEVEL", value_enum)]
+    min_level: Option<Level>,
+
+    /// Only print lines stamped at or after TIME: a date and time like `2026-10-18T12:00`, a
+    /// date, a time today like `12:00`, `today`, `yesterday`, or a time ago like `10 min ago`.
+    /// Files are taken to be in time order and are bisected to find it.
+    #[arg(
+        long,
+        value_name = "TIME",
+        value_parser = timestamp::parse_when,
+        conflicts_with_all = ["num_lines", "bytes"]
+    )]
+    since: Option<DateTime<Utc>>,
+
+    /// Only print lines stamped at or before TIME, given as for --since.
+    #[arg(
+        long,
+        value_name = "TIME",
+        value_parser = timestamp::parse_when,
+        conflicts_with_all = ["num_lines", "bytes"]
+    )]
+    until: Option<DateTime<Utc>>,
+
+    /// The strftime format of the timestamps in lines, e.g. `%d.%m.%Y %H:%M:%S`. By default
+    /// ISO 8601, syslog and access log timestamps are found.
+    #[arg(long, value_name = "FORMAT", value_parser = timestamp::parse_ts_format)]
+    ts_format: Option<String>,
+
+    /// Interleave the lines of all files in the order of their timestamps. The backlogs are
+    /// merged whole; followed lines are held back for --merge-window to be put in order.
+    #[arg(long, action)]
+    merge: bool,
+
+    /// Seconds to hold followed lines back for, with --merge.
+    #[arg(
+        long,
+        value_name = "SECS",
+        value_parser = parse_secs,
+        default_value = "0.5",
+        requires = "merge"
+    )]
+    merge_window: Duration,
+
+    /// Read a file's rotated generations, like app.log.2.gz and app.log.1, ahead of it, so
+    /// that -n, -c and --since reach back across rotations. A +N offset counts from the start
+    /// of the oldest.
+    #[arg(long, action)]
+    include_rotated: bool,
+
+    /// Group lines into multi-line records that start at lines matching REGEX. Records are
+    /// sieved, counted and printed whole.
+    #[arg(long, value_name = "REGEX", conflicts_with = "records")]
+    record_start: Option<String>,
+
+    /// Group lines into records of a common shape, e.g. stack traces.
+    #[arg(long, value_enum, value_name = "PRESET")]
+    records: Option<Preset>,
+
+    /// Print NUM lines of context after each line that matches the sieve.
+    #[arg(short = 'A', long, value_name = "NUM")]
+    after_context: Option<usize>,
+
+    /// Print NUM lines of context before each line that matches the sieve.
+    #[arg(short = 'B', long, value_name = "NUM")]
+    before_context: Option<usize>,
+
+    /// Print NUM lines of context around each line that matches the sieve.
+    #[arg(short = 'C', long, value_name = "NUM")]
+    context: Option<usize>,
+
+    /// Number of lines from the end to tail, or +N to start from line N. Takes K, M and G
+    /// suffixes.
+    #[arg(short, long, default_value = "5", allow_hyphen_values = true)]
+    num_lines: Count,
+
+    /// Tail the last N bytes instead of lines, or +N to start from byte N. Takes K, M and G
+    /// suffixes.
+    #[arg(
+        short = 'c',
+        long,
+        value_name = "N",
+        allow_hyphen_values = true,
+        conflicts_with = "num_lines"
+    )]
+    bytes: Option<Count>,
+
+    /// Character encoding of the input.
+    #[arg(long, value_enum, default_value_t = Encoding::Utf8)]
+    encoding: Encoding,
+
+    /// How to print bytes that aren't valid UTF-8.
+    #[arg(long, value_enum, default_value_t = Binary::Lossy)]
+    binary: Binary,
+
+    /// Prefix every line with the name of the file it came from.
+    #[arg(long, action)]
+    prefix: bool,
+
+    /// Never print headers giving file names.
     #[arg(short, long, action)]
-    follow: bool,
-
-    /// Phrase to filter new lines with. Will automatically enable [-f --follow]
-    #[arg(short, long, default_value = "")]
-    sieve: String,
-
-    /// Number of lines from the end to tail.
-    #[arg(short, long, default_value = "5")]
-    num_lines: String,
-
-    /// Path of the file to tail/follow.
-    #[arg(default_value = "")]
-    file: String,
+    quiet: bool,
+
+    /// Take keys while following, like less +F: space pauses the output and holds back new
+    /// lines, the arrow and page keys scroll back through what was printed, / searches it,
+    /// and space again resumes. q quits.
+    #[arg(long, action)]
+    #[cfg_attr(feature = "tui", arg(conflicts_with = "tui"))]
+    interactive: bool,
+
+    /// Show the lines full-screen, with a scrollback buffer, pausing, search, and a sieve that
+    /// can be edited as lines come in. Implies --follow; -n is how much of the backlog is
+    /// loaded. Lines are sieved one at a time, so it doesn't take records or context.
+    #[cfg(feature = "tui")]
+    #[arg(
+        long,
+        action,
+        conflicts_with_all = ["records", "record_start", "context", "before_context", "after_context"]
+    )]
+    tui: bool,
+
+    /// Paths or glob patterns of the files to tail/follow.
+    file: Vec<String>,
 }
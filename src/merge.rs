@@ -0,0 +1,97 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::timestamp::Stamps;
+
+// A printed line waiting its turn. Lines are ordered by timestamp, and lines with the same
+// one by the order they came in.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Held {
+    ts: Option<DateTime<Utc>>,
+    seq: u64,
+    idx: usize,
+    text: String,
+    arrived: Instant,
+}
+
+/// Holds printed lines back so that the lines of several files come out in the order of
+/// their timestamps, rather than in the order they were read. The backlog of every file is
+/// held until all of them are read; a followed line is held for `window` after it arrives.
+pub struct Merge {
+    stamps: Stamps,
+    window: Duration,
+    held: BinaryHeap<Reverse<Held>>,
+    // The last timestamp of each file. A line without one, like the rest of a stack trace,
+    // is given it, so it stays after the line it belongs to.
+    last: Vec<Option<DateTime<Utc>>>,
+    seq: u64,
+}
+
+impl Merge {
+    pub fn new(stamps: Stamps, window: Duration, files: usize) -> Self {
+        Self {
+            stamps,
+            window,
+            held: BinaryHeap::new(),
+            last: vec![None; files],
+            seq: 0,
+        }
+    }
+
+    /// Hold `text`, the printed form of `line` from file `idx`.
+    pub fn push(&mut self, idx: usize, line: &[u8], text: String) {
+        if let Some(ts) = self.stamps.find(line) {
+            self.last[idx] = Some(ts);
+        }
+        self.seq += 1;
+        self.held.push(Reverse(Held {
+            ts: self.last[idx],
+            seq: self.seq,
+            idx,
+            text,
+            arrived: Instant::now(),
+        }));
+    }
+
+    /// The held lines that are due, with the file each came from, in time order. A line is
+    /// due once it has been held for the window, and so is every line stamped before it.
+    /// With `all`, every line is.
+    pub fn release(&mut self, all: bool) -> Vec<(usize, String)> {
+        let cutoff = if all {
+            self.held.iter().map(|h| (h.0.ts, h.0.seq)).max()
+        } else {
+            self.held
+                .iter()
+                .filter(|h| h.0.arrived.elapsed() >= self.window)
+                .map(|h| (h.0.ts, h.0.seq))
+                .max()
+        };
+        let Some(cutoff) = cutoff else {
+            return Vec::new();
+        };
+
+        let mut due = Vec::new();
+        while self
+            .held
+            .peek()
+            .is_some_and(|h| (h.0.ts, h.0.seq) <= cutoff)
+        {
+            let Reverse(held) = self.held.pop().unwrap();
+            due.push((held.idx, held.text));
+        }
+        due
+    }
+
+    /// How long until the next held line is due.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.held
+            .iter()
+            .map(|h| self.window.saturating_sub(h.0.arrived.elapsed()))
+            .min()
+    }
+}
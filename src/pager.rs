@@ -0,0 +1,527 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    sync::LazyLock,
+};
+
+use colored::Colorize;
+use crossterm::{
+    cursor::MoveTo,
+    event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    terminal::{self, Clear, ClearType},
+};
+use regex::Regex;
+
+/// How many printed lines are kept to scroll back through; the oldest are dropped past it.
+pub const SCROLLBACK: usize = 100_000;
+
+// The colour codes in printed lines, left out when searching and measuring them.
+static ANSI: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\x1b\[[0-9;]*m").unwrap());
+
+const HELP: &str = "space resume  ↑ ↓ PgUp PgDn g G scroll  / search  n N next/prev  q quit";
+
+/// What is printed goes through here on its way to the terminal, so it can be paused and
+/// scrolled back through like `less +F`. While paused, new lines are kept back and the
+/// screen shows the scrollback with a status line under it; resuming shows them.
+pub struct Pager {
+    out: Box<dyn Write + Send>,
+    // The terminal's columns and rows, if fixed rather than asked for on every draw.
+    size: Option<(usize, usize)>,
+    // What was printed, a line each.
+    lines: VecDeque<String>,
+    // The line being printed, until it's ended.
+    partial: Vec<u8>,
+    // The index of the first line on screen while paused, or None while the output runs.
+    top: Option<usize>,
+    // Lines printed while paused, as last shown in the status line.
+    held: usize,
+    shown_held: usize,
+    // What was searched for, as typed, and compiled.
+    search: Option<(String, Regex)>,
+    // A search being typed.
+    prompt: Option<String>,
+    message: Option<String>,
+    // Problems met while paused, written out once the output runs again.
+    warnings: Vec<String>,
+    // Whether searches are for a regex rather than a literal phrase.
+    regex: bool,
+}
+
+impl Pager {
+    pub fn new(regex: bool) -> Self {
+        Self {
+            out: Box::new(io::stdout()),
+            size: None,
+            lines: VecDeque::new(),
+            partial: Vec::new(),
+            top: None,
+            held: 0,
+            shown_held: 0,
+            search: None,
+            prompt: None,
+            message: None,
+            warnings: Vec::new(),
+            regex,
+        }
+    }
+
+    /// Handle a key press, or redraw after a resize. Returns whether to quit.
+    pub fn input(&mut self, event: &Event) -> io::Result<bool> {
+        match event {
+            Event::Key(key) if key.kind == KeyEventKind::Press => return self.key(*key),
+            Event::Resize(..) if self.top.is_some() => self.draw()?,
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    /// Let the output run again, if it's paused, so whatever it was kept from is shown.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.top.is_some() {
+            self.resume()?;
+        }
+        Ok(())
+    }
+
+    /// Show a problem met while following. It's written to stderr between the lines, or
+    /// while paused, shown on the status line and written once the output runs again.
+    pub fn warn(&mut self, message: &str) -> io::Result<()> {
+        let text = format!("trunk: {}", message);
+        if self.top.is_none() {
+            return write!(io::stderr(), "{}\r\n", text);
+        }
+
+        self.message = Some(text.clone());
+        self.warnings.push(text);
+        match self.prompt {
+            Some(_) => Ok(()),
+            None => self.draw_status(),
+        }
+    }
+
+    fn push(&mut self, line: String) {
+        self.lines.push_back(line);
+        if let Some(top) = &mut self.top {
+            self.held += 1;
+            if self.lines.len() > SCROLLBACK {
+                *top = top.saturating_sub(1);
+            }
+        }
+        if self.lines.len() > SCROLLBACK {
+            self.lines.pop_front();
+        }
+    }
+
+    fn key(&mut self, key: KeyEvent) -> io::Result<bool> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if ctrl && key.code == KeyCode::Char('c') {
+            return Ok(true);
+        }
+
+        if let Some(text) = &mut self.prompt {
+            match key.code {
+                KeyCode::Char('u') if ctrl => text.clear(),
+                KeyCode::Char(c) if !ctrl => text.push(c),
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Esc => self.prompt = None,
+                KeyCode::Enter => {
+                    let text = self.prompt.take().unwrap();
+                    self.apply(text);
+                    return self.draw().map(|_| false);
+                }
+                _ => {}
+            }
+            return self.draw_status().map(|_| false);
+        }
+
+        self.message = None;
+        let Some(top) = self.top else {
+            // While the output runs, only keys that stop it do anything.
+            match key.code {
+                KeyCode::Char('q') => return Ok(true),
+                KeyCode::Char(' ') | KeyCode::Char('p') => self.pause(self.bottom())?,
+                KeyCode::Up | KeyCode::Char('k') => self.pause(self.bottom().saturating_sub(1))?,
+                KeyCode::PageUp | KeyCode::Char('b') => {
+                    let page = self.page();
+                    self.pause(self.bottom().saturating_sub(page))?;
+                }
+                KeyCode::Char('/') => {
+                    self.prompt = Some(String::new());
+                    self.pause(self.bottom())?;
+                }
+                _ => {}
+            }
+            return Ok(false);
+        };
+
+        let page = self.page();
+        let bottom = self.bottom();
+        match key.code {
+            KeyCode::Char('q') => return Ok(true),
+            KeyCode::Char(' ') | KeyCode::Char('p') | KeyCode::Char('F') => self.resume()?,
+            KeyCode::End | KeyCode::Char('G') => self.resume()?,
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_to(top.saturating_sub(1))?,
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_to((top + 1).min(bottom))?,
+            KeyCode::PageUp | KeyCode::Char('b') => self.scroll_to(top.saturating_sub(page))?,
+            KeyCode::PageDown | KeyCode::Char('f') => self.scroll_to((top + page).min(bottom))?,
+            KeyCode::Home | KeyCode::Char('g') => self.scroll_to(0)?,
+            KeyCode::Char('/') => {
+                self.prompt = Some(String::new());
+                self.draw_status()?;
+            }
+            KeyCode::Char('n') => {
+                self.find(top + 1, true);
+                self.draw()?;
+            }
+            KeyCode::Char('N') => {
+                self.find(top, false);
+                self.draw()?;
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    fn pause(&mut self, top: usize) -> io::Result<()> {
+        self.top = Some(top);
+        self.held = 0;
+        self.draw()
+    }
+
+    // Let the output run again, from a screen showing the end of what was printed. Every line
+    // held back is written out, so they all end up in the terminal's own scrollback.
+    fn resume(&mut self) -> io::Result<()> {
+        self.top = None;
+        let paused_at = self.lines.len() - self.held.min(self.lines.len());
+        let from = self.bottom().min(paused_at);
+        self.held = 0;
+        queue!(self.out, Clear(ClearType::All), MoveTo(0, 0))?;
+        for line in self.lines.range(from..) {
+            write!(self.out, "{}\r\n", line)?;
+        }
+        self.out.flush()?;
+        for text in self.warnings.drain(..) {
+            write!(io::stderr(), "{}\r\n", text)?;
+        }
+        self.out.write_all(&self.partial)?;
+        self.out.flush()
+    }
+
+    fn scroll_to(&mut self, top: usize) -> io::Result<()> {
+        self.top = Some(top.min(self.lines.len().saturating_sub(1)));
+        self.draw()
+    }
+
+    // Compile what was typed at the prompt and go to its first match.
+    fn apply(&mut self, text: String) {
+        if text.is_empty() {
+            self.search = None;
+            return;
+        }
+        let pattern = if self.regex {
+            text.clone()
+        } else {
+            regex::escape(&text)
+        };
+        match Regex::new(&pattern) {
+            Ok(re) => {
+                self.search = Some((text, re));
+                // Down from the top of the screen, or failing that, up from it.
+                let top = self.top.unwrap_or(0);
+                self.find(top, true);
+                if self.message.is_some() {
+                    self.message = None;
+                    self.find(top, false);
+                }
+            }
+            Err(e) => self.message = Some(format!("invalid search: {}", e)),
+        }
+    }
+
+    // Put the next line from `from` that matches the search at the top, going down or up.
+    fn find(&mut self, from: usize, down: bool) {
+        let Some((text, search)) = &self.search else {
+            self.message = Some("nothing to search for; / starts a search".to_string());
+            return;
+        };
+
+        let from = from.min(self.lines.len());
+        let hit = |line: &String| search.is_match(&ANSI.replace_all(line, ""));
+        let found = if down {
+            self.lines.range(from..).position(hit).map(|pos| from + pos)
+        } else {
+            self.lines.range(..from).rposition(hit)
+        };
+
+        match found {
+            Some(pos) => self.top = Some(pos),
+            None => self.message = Some(format!("not found: {}", text)),
+        }
+    }
+
+    // The terminal's size, in columns and rows.
+    fn size(&self) -> (usize, usize) {
+        if let Some(size) = self.size {
+            return size;
+        }
+        let (cols, rows) = terminal::size().unwrap_or((80, 24));
+        (cols.max(1) as usize, rows.max(2) as usize)
+    }
+
+    // How many rows the scrollback is shown in, leaving one for the status line.
+    fn page(&self) -> usize {
+        self.size().1 - 1
+    }
+
+    // The rows a line takes up once the terminal has wrapped it.
+    fn rows(line: &str, cols: usize) -> usize {
+        ANSI.replace_all(line, "")
+            .chars()
+            .count()
+            .div_ceil(cols)
+            .max(1)
+    }
+
+    // The first line of the last screenful.
+    fn bottom(&self) -> usize {
+        let (cols, _) = self.size();
+        let mut rows = 0;
+        let mut top = self.lines.len();
+        for line in self.lines.iter().rev() {
+            rows += Self::rows(line, cols);
+            if rows > self.page() {
+                break;
+            }
+            top -= 1;
+        }
+        top
+    }
+
+    // Show the scrollback from the top line down, and the status line under it.
+    fn draw(&mut self) -> io::Result<()> {
+        let Some(top) = self.top else { return Ok(()) };
+        let (cols, _) = self.size();
+        let page = self.page();
+
+        queue!(self.out, Clear(ClearType::All), MoveTo(0, 0))?;
+        let mut rows = 0;
+        for line in self.lines.range(top..) {
+            rows += Self::rows(line, cols);
+            if rows > page {
+                break;
+            }
+            // A line cut off mid-colour mustn't colour the rest of the screen.
+            write!(self.out, "{}\x1b[0m\r\n", line)?;
+        }
+        self.draw_status()
+    }
+
+    // Show the prompt, a message, or how many lines are being held, on the bottom row.
+    fn draw_status(&mut self) -> io::Result<()> {
+        let (cols, rows) = self.size();
+        let status = match (&self.prompt, &self.message) {
+            (Some(text), _) => format!("/{}", text),
+            (None, Some(message)) => message.clone(),
+            (None, None) => format!("PAUSED, {} new lines  {}", self.held, HELP),
+        };
+        let status: String = status.chars().take(cols - 1).collect();
+
+        queue!(
+            self.out,
+            MoveTo(0, rows as u16 - 1),
+            Clear(ClearType::CurrentLine)
+        )?;
+        match self.prompt {
+            Some(_) => write!(self.out, "{}", status)?,
+            None => write!(self.out, "{}", status.reversed())?,
+        }
+        self.shown_held = self.held;
+        self.out.flush()
+    }
+}
+
+impl Write for Pager {
+    // Lines are kept for the scrollback, and written out unless paused. The terminal is in
+    // raw mode while following, so every newline needs a carriage return before it.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for piece in buf.split_inclusive(|&b| b == b'\n') {
+            let (text, ended) = match piece.strip_suffix(b"\n") {
+                Some(text) => (text, true),
+                None => (piece, false),
+            };
+
+            if self.top.is_none() {
+                self.out.write_all(text)?;
+                if ended {
+                    self.out.write_all(b"\r\n")?;
+                }
+            }
+            self.partial.extend_from_slice(text);
+            if ended {
+                let line = String::from_utf8_lossy(&self.partial).into_owned();
+                self.partial.clear();
+                self.push(line);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    // While paused, the count of lines held back is brought up to date.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.top.is_some() && self.prompt.is_none() && self.held != self.shown_held {
+            return self.draw_status();
+        }
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    // What the pager wrote, shared with the test.
+    #[derive(Clone, Default)]
+    struct Screen(Arc<Mutex<Vec<u8>>>);
+
+    impl Screen {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    impl Write for Screen {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn pager(cols: usize, rows: usize) -> (Pager, Screen) {
+        let screen = Screen::default();
+        let pager = Pager {
+            out: Box::new(screen.clone()),
+            size: Some((cols, rows)),
+            ..Pager::new(false)
+        };
+        (pager, screen)
+    }
+
+    fn press(pager: &mut Pager, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                c => KeyCode::Char(c),
+            };
+            let event = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+            pager.input(&event).unwrap();
+        }
+    }
+
+    fn print(pager: &mut Pager, lines: impl IntoIterator<Item = String>) {
+        for line in lines {
+            writeln!(pager, "{}", line).unwrap();
+        }
+    }
+
+    #[test]
+    fn lines_are_held_back_while_paused() {
+        let (mut pager, screen) = pager(80, 5);
+        pager.write_all(b"one\ntw").unwrap();
+        assert_eq!(screen.take(), "one\r\ntw");
+        pager.write_all(b"o\n").unwrap();
+        assert_eq!(pager.lines, ["one", "two"]);
+
+        press(&mut pager, " ");
+        screen.take();
+        print(&mut pager, ["three".to_string(), "four".to_string()]);
+        assert_eq!(screen.take(), "");
+        assert_eq!(pager.held, 2);
+        pager.flush().unwrap();
+        assert!(screen.take().contains("PAUSED, 2 new lines"));
+
+        press(&mut pager, " ");
+        assert_eq!(pager.top, None);
+        let shown = screen.take();
+        assert!(shown.contains("three\r\nfour\r\n"));
+
+        // More lines than fit on the screen are all written out once it runs again.
+        press(&mut pager, " ");
+        print(&mut pager, (0..10).map(|i| format!("held {}", i)));
+        screen.take();
+        press(&mut pager, " ");
+        let shown = screen.take();
+        let written: String = (0..10).map(|i| format!("held {}\r\n", i)).collect();
+        assert!(shown.ends_with(&written));
+    }
+
+    #[test]
+    fn scrollback_drops_the_oldest_lines() {
+        let (mut pager, _) = pager(80, 5);
+        print(&mut pager, (0..10).map(|i| format!("line {}", i)));
+
+        // Scrolled to the sixth line, it stays on screen as older lines are dropped.
+        press(&mut pager, " gjjjjj");
+        assert_eq!(pager.top, Some(5));
+        print(
+            &mut pager,
+            (10..SCROLLBACK + 3).map(|i| format!("line {}", i)),
+        );
+
+        assert_eq!(pager.lines.len(), SCROLLBACK);
+        assert_eq!(pager.lines[0], "line 3");
+        assert_eq!(pager.top, Some(2));
+        assert_eq!(pager.lines[2], "line 5");
+        assert_eq!(pager.held, SCROLLBACK - 7);
+    }
+
+    #[test]
+    fn search_looks_down_then_up() {
+        let (mut pager, _) = pager(80, 5);
+        print(
+            &mut pager,
+            (0..10).map(|i| match i {
+                2 | 7 => format!("\x1b[31mmatch\x1b[0m {}", i),
+                _ => format!("line {}", i),
+            }),
+        );
+
+        // Searching pauses with the last screenful shown, which starts at the seventh line.
+        press(&mut pager, "/match\n");
+        assert_eq!(pager.top, Some(7));
+        press(&mut pager, "n");
+        assert_eq!(pager.message.as_deref(), Some("not found: match"));
+        assert_eq!(pager.top, Some(7));
+        press(&mut pager, "N");
+        assert_eq!(pager.top, Some(2));
+
+        // With nothing below, the nearest match above is found.
+        press(&mut pager, "G");
+        press(&mut pager, "/match 2\n");
+        assert_eq!(pager.top, Some(2));
+
+        // Colour codes aren't searched.
+        press(&mut pager, "/31m\n");
+        assert_eq!(pager.message.as_deref(), Some("not found: 31m"));
+    }
+
+    #[test]
+    fn bottom_counts_wrapped_rows() {
+        let (mut pager, _) = pager(10, 5);
+        print(
+            &mut pager,
+            ["first", "a line long enough to wrap", "last"].map(String::from),
+        );
+
+        // Four rows are left above the status line: the last line and three for the long one.
+        assert_eq!(pager.bottom(), 1);
+    }
+}
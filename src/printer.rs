@@ -0,0 +1,487 @@
+use std::{
+    borrow::Cow,
+    cell::OnceCell,
+    io::{self, BufWriter, Stdout, Write},
+    sync::mpsc::Sender,
+    time::Duration,
+};
+
+use colored::{Color, Colorize};
+use crossterm::event::Event;
+
+use crate::context::{Context, Window};
+use crate::decode::Binary;
+use crate::fields::{Fields, Record};
+use crate::level::Level;
+use crate::merge::Merge;
+use crate::pager::Pager;
+use crate::records::{Pending, Records, SETTLE};
+use crate::sieve::Sieve;
+use crate::timestamp::Span;
+
+/// Colours cycled through for per-file prefixes.
+pub const PREFIX_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Red,
+];
+
+/// How lines are attributed to the file they came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Label {
+    None,
+    /// `==> name <==` whenever output switches to another file, like `tail a b`.
+    Header,
+    /// A coloured `[name]` in front of every line.
+    Prefix,
+}
+
+/// What a captured printer sends on in place of writing it out.
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+pub enum Captured {
+    /// A line, or the lines of a record, with the index of the file it came from.
+    Line(usize, String),
+    /// A problem met while following, for the TUI to show.
+    Warning(String),
+}
+
+/// A line, or a record of lines, along with its fields once they've been parsed, so the
+/// filters and the compact layout don't each parse it again.
+pub struct Line<'a> {
+    text: Cow<'a, [u8]>,
+    record: OnceCell<Option<Record>>,
+}
+
+impl<'a> Line<'a> {
+    pub fn new(text: impl Into<Cow<'a, [u8]>>) -> Self {
+        Self {
+            text: text.into(),
+            record: OnceCell::new(),
+        }
+    }
+
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+
+    /// Drop the first `len` bytes, and with them whatever was parsed from the whole.
+    pub fn cut(&mut self, len: usize) {
+        self.text.to_mut().drain(..len);
+        self.record = OnceCell::new();
+    }
+}
+
+/// What lines are kept by, besides the sieve.
+pub struct Filters {
+    /// Structured mode, with its field predicates.
+    pub fields: Option<Fields>,
+    pub min_level: Option<Level>,
+    pub span: Option<Span>,
+}
+
+impl Filters {
+    // Whether the line satisfies any field predicates, the minimum level and the time span.
+    // Lines without a level, like the rest of a stack trace, are never dropped for it.
+    fn keeps(&self, line: &Line) -> bool {
+        self.fields
+            .as_ref()
+            .zip(self.record(line))
+            .is_none_or(|(fields, record)| fields.keeps(record))
+            && self
+                .min_level
+                .is_none_or(|min| self.level(line).is_none_or(|level| level >= min))
+            && self
+                .span
+                .as_ref()
+                .is_none_or(|span| span.contains(&line.text))
+    }
+
+    // A line's fields in structured mode, parsed the first time they're asked for. None if
+    // it doesn't parse.
+    fn record<'a>(&self, line: &'a Line) -> Option<&'a Record> {
+        let fields = self.fields.as_ref()?;
+        line.record
+            .get_or_init(|| fields.parse(&line.text))
+            .as_ref()
+    }
+
+    // Whether lines are picked out by anything that context could be shown around.
+    fn select(&self) -> bool {
+        self.fields.as_ref().is_some_and(Fields::filters) || self.min_level.is_some()
+    }
+
+    // The level of a line, from its level field in structured mode or else from its text.
+    fn level(&self, line: &Line) -> Option<Level> {
+        self.record(line)
+            .and_then(Fields::level)
+            .or_else(|| Level::detect(&line.text))
+    }
+}
+
+/// Where printed lines are written.
+enum Output {
+    Stdout(Stdout),
+    Pager(Box<Pager>),
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(out) => out.write(buf),
+            Output::Pager(pager) => pager.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(out) => out.flush(),
+            Output::Pager(pager) => pager.flush(),
+        }
+    }
+}
+
+pub struct Printer {
+    label: Label,
+    names: Vec<String>,
+    // Index of the file whose lines were printed last.
+    last: Option<usize>,
+    pub printed: u64,
+    binary: Binary,
+    context: Context,
+    // One per file, so context never runs across files.
+    windows: Vec<Window>,
+    records: Option<Records>,
+    filters: Filters,
+    merge: Option<Merge>,
+    // The record each file is in the middle of.
+    pending: Vec<Pending>,
+    // Buffered so a large backlog isn't written a line per syscall; see `flush`.
+    out: BufWriter<Output>,
+    // Where lines go instead of stdout, with the file each came from, once captured.
+    capture: Option<Sender<Captured>>,
+}
+
+impl Printer {
+    pub fn new(
+        label: Label,
+        names: Vec<String>,
+        binary: Binary,
+        context: Context,
+        records: Option<Records>,
+        filters: Filters,
+        merge: Option<Merge>,
+    ) -> Self {
+        let windows = names.iter().map(|_| Window::default()).collect();
+        let pending = names.iter().map(|_| Pending::default()).collect();
+        Self {
+            label,
+            names,
+            last: None,
+            printed: 0,
+            binary,
+            context,
+            windows,
+            records,
+            filters,
+            merge,
+            pending,
+            out: BufWriter::with_capacity(64 * 1024, Output::Stdout(io::stdout())),
+            capture: None,
+        }
+    }
+
+    /// Send every line to `tx` from now on, rather than writing it out. Files are told apart
+    /// by the index sent along with each line, so there are no headers or prefixes.
+    #[cfg(feature = "tui")]
+    pub fn capture(&mut self, tx: Sender<Captured>) {
+        self.capture = Some(tx);
+    }
+
+    fn write(out: &mut BufWriter<Output>, args: std::fmt::Arguments) {
+        if let Err(e) = out.write_fmt(args) {
+            exit_on_write_error(e);
+        }
+    }
+
+    /// Write lines through `pager` from now on, so the output can be paused and scrolled.
+    pub fn page(&mut self, pager: Pager) {
+        self.flush();
+        self.out = BufWriter::with_capacity(64 * 1024, Output::Pager(Box::new(pager)));
+    }
+
+    /// Whether lines are written through a pager, which wants keys passed to `input`.
+    pub fn paged(&self) -> bool {
+        matches!(self.out.get_ref(), Output::Pager(_))
+    }
+
+    /// Pass a key press or resize to the pager, once everything printed so far has reached
+    /// it. Returns whether to quit.
+    pub fn input(&mut self, event: &Event) -> bool {
+        self.flush();
+        let Output::Pager(pager) = self.out.get_mut() else {
+            return false;
+        };
+        pager
+            .input(event)
+            .unwrap_or_else(|e| exit_on_write_error(e))
+    }
+
+    /// Report a problem met while following, like a file that can't be read, without
+    /// garbling the screen: it goes to the TUI, or through the pager, or else to stderr.
+    pub fn warn(&mut self, message: &str) {
+        if let Some(tx) = &self.capture {
+            let _ = tx.send(Captured::Warning(message.to_string()));
+            return;
+        }
+
+        // Whatever was printed before the problem shows up before it.
+        if let Err(e) = self.out.flush() {
+            exit_on_write_error(e);
+        }
+        match self.out.get_mut() {
+            Output::Pager(pager) => {
+                if let Err(e) = pager.warn(message) {
+                    exit_on_write_error(e);
+                }
+            }
+            Output::Stdout(_) => eprintln!("trunk: {}", message),
+        }
+    }
+
+    /// Let paged output run again, if it's paused. Called when following stops.
+    pub fn unpause(&mut self) {
+        self.flush();
+        if let Output::Pager(pager) = self.out.get_mut() {
+            if let Err(e) = pager.finish() {
+                exit_on_write_error(e);
+            }
+        }
+    }
+
+    /// Push out everything printed so far. Called after every batch of followed lines. Lines
+    /// held back to be merged are only let out once they're due.
+    pub fn flush(&mut self) {
+        self.release(false);
+        if let Err(e) = self.out.flush() {
+            exit_on_write_error(e);
+        }
+    }
+
+    /// Push out everything printed so far, including every line held back to be merged.
+    /// Called once the backlog is done and when following stops.
+    pub fn flush_all(&mut self) {
+        self.release(true);
+        self.flush();
+    }
+
+    fn release(&mut self, all: bool) {
+        let Some(merge) = &mut self.merge else { return };
+        for (idx, text) in merge.release(all) {
+            self.line(idx, &text);
+        }
+    }
+
+    /// How long until the next line held back to be merged is due.
+    pub fn merge_timeout(&self) -> Option<Duration> {
+        self.merge.as_ref().and_then(Merge::timeout)
+    }
+
+    /// Print the header for file `idx`, even if it was the last one printed.
+    pub fn header(&mut self, idx: usize) {
+        if self.label == Label::Header && self.capture.is_none() {
+            if self.last.is_some() {
+                Self::write(&mut self.out, format_args!("\n"));
+            }
+            Self::write(&mut self.out, format_args!("==> {} <==\n", self.names[idx]));
+        }
+        self.last = Some(idx);
+    }
+
+    /// Print a line, or the lines of a record, as they are.
+    pub fn line(&mut self, idx: usize, line: &str) {
+        self.printed += line.matches('\n').count() as u64 + 1;
+        self.write_line(idx, line);
+    }
+
+    /// Print a note about file `idx`, like it having been truncated. It isn't a line of the
+    /// file, so it isn't counted as one printed.
+    pub fn banner(&mut self, idx: usize, text: &str) {
+        self.write_line(idx, text);
+    }
+
+    fn write_line(&mut self, idx: usize, line: &str) {
+        if let Some(tx) = &self.capture {
+            // Nothing is left to see the line once the receiving end is gone.
+            let _ = tx.send(Captured::Line(idx, line.to_string()));
+            return;
+        }
+
+        if self.last != Some(idx) {
+            self.header(idx);
+        }
+
+        if self.label == Label::Prefix {
+            let color = PREFIX_COLORS[idx % PREFIX_COLORS.len()];
+            let prefix = format!("[{}]", self.names[idx]);
+            for line in line.split('\n') {
+                Self::write(
+                    &mut self.out,
+                    format_args!("{} {}\n", prefix.color(color), line),
+                );
+            }
+        } else {
+            Self::write(&mut self.out, format_args!("{}\n", line));
+        }
+    }
+
+    // Print a line from file `idx`, or hold it back to be merged with the other files.
+    fn print(&mut self, idx: usize, line: &Line, text: String) {
+        match &mut self.merge {
+            Some(merge) => merge.push(idx, &line.text, text),
+            None => self.line(idx, &text),
+        }
+    }
+
+    /// Whether a decoded line would be printed by `emit`.
+    pub fn passes(&self, line: &Line, sieve: Option<&Sieve>) -> bool {
+        self.binary.keep(&line.text) && self.matches(line, sieve)
+    }
+
+    // Whether the line gets through the sieve and the other filters.
+    fn matches(&self, line: &Line, sieve: Option<&Sieve>) -> bool {
+        sieve.is_none_or(|sieve| sieve.is_match(&line.text)) && self.filters.keeps(line)
+    }
+
+    /// The time span lines are limited to, if any.
+    pub fn span(&self) -> Option<&Span> {
+        self.filters.span.as_ref()
+    }
+
+    // The text printed for a line: structured lines in their compact layout, anything else
+    // coloured by its level with the sieve's matches highlighted.
+    fn render(&self, line: &Line, sieve: Option<&Sieve>) -> String {
+        if let Some((fields, record)) = self.filters.fields.as_ref().zip(self.filters.record(line))
+        {
+            return fields.render(record);
+        }
+
+        let color = self.filters.level(line).and_then(Level::line_color);
+        let text = &line.text;
+        match (sieve, color) {
+            (Some(sieve), _) => sieve.highlight(text, self.binary, color),
+            (None, Some(color)) => self.binary.display(text).color(color).to_string(),
+            (None, None) => self.binary.display(text).into_owned(),
+        }
+    }
+
+    /// Whether the line starts a record, which every line does unless records are grouped.
+    pub fn starts_record(&self, line: &[u8]) -> bool {
+        self.records.as_ref().is_none_or(|r| r.starts_record(line))
+    }
+
+    /// Group a line into the record being gathered in `pending`, returning the record it
+    /// completes. Without records every line is one.
+    pub fn gather(&self, pending: &mut Pending, line: Vec<u8>) -> Option<Vec<u8>> {
+        match &self.records {
+            Some(records) => records.push(pending, line),
+            None => Some(line),
+        }
+    }
+
+    /// Print a decoded line from file `idx` if it gets through the sieve. When lines are
+    /// grouped into records, it's the record that is sieved once it's complete, i.e. once the
+    /// next one starts or `finish_records` is called.
+    pub fn emit(&mut self, idx: usize, line: &[u8], sieve: Option<&Sieve>) {
+        let Some(records) = &self.records else {
+            return self.sift(idx, &Line::new(line), sieve);
+        };
+
+        if let Some(record) = records.push(&mut self.pending[idx], line.to_vec()) {
+            self.sift(idx, &Line::new(record), sieve);
+        }
+    }
+
+    /// Print a line, or a record gathered by `gather`, that was held back after `passes`
+    /// looked at it, without parsing it again.
+    pub fn emit_held(&mut self, idx: usize, line: Line, sieve: Option<&Sieve>) {
+        self.sift(idx, &line, sieve);
+    }
+
+    /// Print the records whose files have gone quiet, or with `all`, every one still pending.
+    pub fn finish_records(&mut self, all: bool, sieve: Option<&Sieve>) {
+        for idx in 0..self.pending.len() {
+            if all || self.pending[idx].settled() {
+                if let Some(record) = self.pending[idx].take() {
+                    self.sift(idx, &Line::new(record), sieve);
+                }
+            }
+        }
+    }
+
+    /// How long until the next pending record is taken as complete.
+    pub fn record_timeout(&self) -> Option<Duration> {
+        self.pending
+            .iter()
+            .filter_map(|p| p.since)
+            .map(|since| SETTLE.saturating_sub(since.elapsed()))
+            .min()
+    }
+
+    // Print a line or record if it gets through the sieve. With context, lines around a match
+    // are printed along with it, and `--` goes between groups of lines that aren't adjacent.
+    fn sift(&mut self, idx: usize, line: &Line, sieve: Option<&Sieve>) {
+        let context = self.context;
+        let filtered = sieve.is_some() || self.filters.select();
+        if context.is_empty() || !filtered {
+            if self.passes(line, sieve) {
+                let text = self.render(line, sieve);
+                self.print(idx, line, text);
+            }
+            return;
+        }
+
+        if !self.binary.keep(&line.text) {
+            self.windows[idx].skip();
+            return;
+        }
+
+        if !self.matches(line, sieve) {
+            if self.windows[idx].unmatched(&line.text, context) {
+                let text = self.render(line, None);
+                self.print(idx, line, text);
+            }
+            return;
+        }
+
+        // Once merged, groups of lines don't stay together, so they aren't separated.
+        let (separate, held) = self.windows[idx].matched(context);
+        if separate && self.merge.is_none() {
+            self.write_line(idx, "--");
+        }
+        for held in held {
+            let held = Line::new(held);
+            let text = self.render(&held, None);
+            self.print(idx, &held, text);
+        }
+        let text = self.render(line, sieve);
+        self.print(idx, line, text);
+    }
+
+    /// Lines of file `idx` were left out before reaching the printer, so context doesn't
+    /// bridge them.
+    pub fn skip(&mut self, idx: usize) {
+        self.windows[idx].skip();
+    }
+}
+
+// A closed pipe (e.g. `trunk app.log | head`) is a normal way for output to end.
+fn exit_on_write_error(e: io::Error) -> ! {
+    if e.kind() == io::ErrorKind::BrokenPipe {
+        std::process::exit(0);
+    }
+    eprintln!("trunk: cannot write output: {}", e);
+    std::process::exit(1);
+}
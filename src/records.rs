@@ -0,0 +1,112 @@
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use regex::bytes::Regex;
+
+/// A record still being gathered is taken to be complete once nothing has been added to it
+/// for this long, so the last one in a followed file isn't held forever.
+pub const SETTLE: Duration = Duration::from_millis(200);
+
+/// Common shapes of multi-line records.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// Java stack traces: `at ...` frames, `Caused by:` and exception lines continue a record.
+    Java,
+    /// Python tracebacks, up to and including the exception line.
+    Python,
+    /// A record starts with a timestamp like `2024-01-31 12:00:00` or `Jan 31 12:00:00`.
+    Timestamp,
+    /// Indented lines and closing brackets continue a record, e.g. pretty printed JSON.
+    Indent,
+}
+
+/// Where multi-line records start. A line starts a record if it matches `start` and not
+/// `continuation`; any other line is added to the record before it.
+#[derive(Clone)]
+pub struct Records {
+    start: Option<Regex>,
+    continuation: Option<Regex>,
+}
+
+impl Records {
+    pub fn new(start: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            start: Some(Regex::new(start)?),
+            continuation: None,
+        })
+    }
+
+    pub fn preset(preset: Preset) -> Self {
+        let (start, continuation) = match preset {
+            Preset::Java => (
+                None,
+                Some(r"^(\s|Caused by: |[\w$.]+(Exception|Error|Throwable)\b)"),
+            ),
+            Preset::Python => (
+                None,
+                Some(
+                    r"^(\s|Traceback \(most recent call last\):|During handling of the above exception|The above exception was the direct cause|[\w.]+(Error|Exception|Warning|Exit|Interrupt)\b)",
+                ),
+            ),
+            Preset::Timestamp => (
+                Some(
+                    r"^\[?(\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}|[A-Z][a-z]{2} +\d+ \d{2}:\d{2}:\d{2}|\d{2}:\d{2}:\d{2})",
+                ),
+                None,
+            ),
+            Preset::Indent => (None, Some(r"^(\s|[}\]])")),
+        };
+
+        // The presets are fixed and known to compile.
+        let compile = |re: &str| Regex::new(re).unwrap();
+        Self {
+            start: start.map(compile),
+            continuation: continuation.map(compile),
+        }
+    }
+
+    /// Whether the line starts a new record. Only the first line of an already gathered
+    /// record is looked at.
+    pub fn starts_record(&self, line: &[u8]) -> bool {
+        let first = line.split(|&b| b == b'\n').next().unwrap_or(line);
+        self.start.as_ref().is_none_or(|re| re.is_match(first))
+            && !self
+                .continuation
+                .as_ref()
+                .is_some_and(|re| re.is_match(first))
+    }
+
+    /// Add a line to the record being gathered in `pending`. Returns the previous record if
+    /// this line starts a new one. Lines before the first start make up a record of their own.
+    pub fn push(&self, pending: &mut Pending, line: Vec<u8>) -> Option<Vec<u8>> {
+        pending.since = Some(Instant::now());
+        match &mut pending.record {
+            Some(record) if !self.starts_record(&line) => {
+                record.push(b'\n');
+                record.extend_from_slice(&line);
+                None
+            }
+            record => record.replace(line),
+        }
+    }
+}
+
+/// A record of one input that is still being gathered.
+#[derive(Default)]
+pub struct Pending {
+    record: Option<Vec<u8>>,
+    // When the last line was added.
+    pub since: Option<Instant>,
+}
+
+impl Pending {
+    pub fn take(&mut self) -> Option<Vec<u8>> {
+        self.since = None;
+        self.record.take()
+    }
+
+    /// Whether the record has waited long enough to be taken as complete.
+    pub fn settled(&self) -> bool {
+        self.since.is_some_and(|since| since.elapsed() >= SETTLE)
+    }
+}
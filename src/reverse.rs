@@ -0,0 +1,95 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+use crate::decode::Encoding;
+
+// How much of the file is pulled in per backwards step. Steps double while no newline turns
+// up, so very long lines don't mean re-copying the buffer once per block.
+const BLOCK_SIZE: u64 = 64 * 1024;
+const MAX_BLOCK_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Walks a file from `end` towards the start, yielding `(offset, line)` for each line
+/// without its trailing newline. Only the blocks needed to reach the yielded lines are read.
+pub struct RevLines<'a> {
+    f: &'a mut File,
+    // Start of the bytes held in `buf`; everything before it is still unread.
+    pos: u64,
+    buf: Vec<u8>,
+    pending: bool,
+    encoding: Encoding,
+    step: u64,
+}
+
+impl<'a> RevLines<'a> {
+    pub fn new(f: &'a mut File, end: u64, encoding: Encoding) -> io::Result<Self> {
+        let mut ret = Self {
+            f,
+            pos: end,
+            buf: Vec::new(),
+            pending: end > 0,
+            encoding,
+            step: BLOCK_SIZE,
+        };
+
+        // A trailing newline terminates the last line rather than starting an empty one.
+        if end > 0 {
+            ret.fill()?;
+            let nl = encoding.newline_len();
+            if ret.buf.len() >= nl
+                && encoding.rfind_newline(&ret.buf, ret.pos) == Some(ret.buf.len() - nl)
+            {
+                ret.buf.truncate(ret.buf.len() - nl);
+            }
+        }
+
+        Ok(ret)
+    }
+
+    // Prepend the previous block to the buffer.
+    fn fill(&mut self) -> io::Result<()> {
+        let start = self.pos.saturating_sub(self.step);
+        let mut block = vec![0; (self.pos - start) as usize];
+
+        self.f.seek(SeekFrom::Start(start))?;
+        self.f.read_exact(&mut block)?;
+
+        block.extend_from_slice(&self.buf);
+        self.buf = block;
+        self.pos = start;
+        Ok(())
+    }
+
+    fn next_line(&mut self) -> io::Result<Option<(u64, Vec<u8>)>> {
+        if !self.pending {
+            return Ok(None);
+        }
+
+        loop {
+            if let Some(i) = self.encoding.rfind_newline(&self.buf, self.pos) {
+                let nl = self.encoding.newline_len();
+                let line = self.buf.split_off(i + nl);
+                self.buf.truncate(i);
+                self.step = BLOCK_SIZE;
+                return Ok(Some((self.pos + (i + nl) as u64, line)));
+            }
+
+            if self.pos == 0 {
+                self.pending = false;
+                return Ok(Some((0, std::mem::take(&mut self.buf))));
+            }
+
+            self.fill()?;
+            self.step = (self.step * 2).min(MAX_BLOCK_SIZE);
+        }
+    }
+}
+
+impl Iterator for RevLines<'_> {
+    type Item = io::Result<(u64, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_line().transpose()
+    }
+}
@@ -0,0 +1,167 @@
+use colored::{Color, Colorize};
+use regex::bytes::Regex;
+
+use crate::decode::Binary;
+
+// Colours cycled through for each positive sieve term.
+const TERM_COLORS: [Color; 6] = [
+    Color::Red,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+];
+
+// Colours cycled through for capture groups when --groups is set.
+const GROUP_COLORS: [Color; 5] = [
+    Color::Yellow,
+    Color::Green,
+    Color::Cyan,
+    Color::Magenta,
+    Color::Blue,
+];
+
+/// How positive sieve terms are combined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Combinator {
+    Any,
+    All,
+}
+
+pub struct Sieve {
+    terms: Vec<Regex>,
+    excludes: Vec<Regex>,
+    groups: bool,
+    combinator: Combinator,
+}
+
+/// A coloured region of a line, in byte offsets.
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub color: Color,
+}
+
+impl Sieve {
+    /// Compile a sieve. Literal phrases are escaped so both modes share the same matcher.
+    pub fn new(
+        terms: &[String],
+        excludes: &[String],
+        regex: bool,
+        groups: bool,
+        combinator: Combinator,
+    ) -> Result<Self, regex::Error> {
+        let compile = |pattern: &String| {
+            if regex {
+                Regex::new(pattern)
+            } else {
+                Regex::new(&regex::escape(pattern))
+            }
+        };
+
+        Ok(Self {
+            terms: terms.iter().map(compile).collect::<Result<_, _>>()?,
+            excludes: excludes.iter().map(compile).collect::<Result<_, _>>()?,
+            groups,
+            combinator,
+        })
+    }
+
+    /// A line passes if no exclude matches and the positive terms match per the combinator.
+    /// With no positive terms every line that isn't excluded passes.
+    /// Matching is done on the raw bytes, so lines that aren't valid UTF-8 can still match.
+    pub fn is_match(&self, line: &[u8]) -> bool {
+        if self.excludes.iter().any(|re| re.is_match(line)) {
+            return false;
+        }
+
+        match self.combinator {
+            _ if self.terms.is_empty() => true,
+            Combinator::Any => self.terms.iter().any(|re| re.is_match(line)),
+            Combinator::All => self.terms.iter().all(|re| re.is_match(line)),
+        }
+    }
+
+    /// Colour the matches of every positive term in that term's colour, and each capture
+    /// group in its own colour if enabled. The text between matches is in `base`, if given.
+    pub fn highlight(&self, line: &[u8], binary: Binary, base: Option<Color>) -> String {
+        let plain = |bytes: &[u8]| {
+            let text = binary.display(bytes);
+            match base {
+                Some(color) if !text.is_empty() => text.color(color).to_string(),
+                _ => text.into_owned(),
+            }
+        };
+
+        let mut out = String::with_capacity(line.len());
+        let mut last = 0;
+        for span in self.spans(line) {
+            out.push_str(&plain(&line[last..span.start]));
+            let text = binary.display(&line[span.start..span.end]);
+            out.push_str(&text.color(span.color).to_string());
+            last = span.end;
+        }
+
+        out.push_str(&plain(&line[last..]));
+        out
+    }
+
+    /// Where the positive terms match, and in what colour, in order. Where matches overlap
+    /// the earliest one wins.
+    pub fn spans(&self, line: &[u8]) -> Vec<Span> {
+        let mut spans = Vec::new();
+
+        for (i, re) in self.terms.iter().enumerate() {
+            let color = TERM_COLORS[i % TERM_COLORS.len()];
+
+            for caps in re.captures_iter(line) {
+                let m = caps.get(0).unwrap();
+                if m.is_empty() {
+                    continue;
+                }
+
+                let mut pos = m.start();
+                if self.groups {
+                    // Nested or overlapping groups are skipped, the outermost one wins.
+                    for (j, group) in caps.iter().enumerate().skip(1) {
+                        let Some(g) = group else { continue };
+                        if g.start() < pos || g.is_empty() {
+                            continue;
+                        }
+
+                        spans.push(Span {
+                            start: pos,
+                            end: g.start(),
+                            color,
+                        });
+                        spans.push(Span {
+                            start: g.start(),
+                            end: g.end(),
+                            color: GROUP_COLORS[(j - 1) % GROUP_COLORS.len()],
+                        });
+                        pos = g.end();
+                    }
+                }
+
+                spans.push(Span {
+                    start: pos,
+                    end: m.end(),
+                    color,
+                });
+            }
+        }
+
+        // Stable sort keeps a match's pieces in order; empty and overlapped ones are dropped.
+        spans.sort_by_key(|s| s.start);
+        let mut last = 0;
+        spans.retain(|span| {
+            let keep = span.start >= last && span.start < span.end;
+            if keep {
+                last = span.end;
+            }
+            keep
+        });
+        spans
+    }
+}
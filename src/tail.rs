@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+
+use crate::context::Context;
+use crate::count::{Count, Unit};
+use crate::printer::Line;
+
+// A line held for printing, or a marker for where lines were dropped from between them.
+enum Held {
+    Line { line: Line<'static>, matched: bool },
+    Gap,
+}
+
+/// The end of input that can only be read forwards, measured like `-n` or `-c`. For the
+/// last N lines or bytes, older lines are dropped as new ones come in, so memory is bounded
+/// by the count, not the input. Past a `+N` offset lines are handed straight back instead.
+pub struct Tail {
+    unit: Unit,
+    count: Count,
+    context: Context,
+    held: VecDeque<Held>,
+    // Bytes held, counting the newline each line is printed with.
+    bytes: u64,
+    // Matching lines held.
+    matches: u64,
+    // Lines since the last match, for context.
+    since_match: usize,
+    // Lines or bytes seen so far, for `+N`.
+    seen: u64,
+    // Whether the last line taken ended the input without a newline.
+    unterminated: bool,
+}
+
+impl Tail {
+    pub fn new(unit: Unit, count: Count, context: Context) -> Self {
+        Self {
+            unit,
+            count,
+            context,
+            held: VecDeque::new(),
+            bytes: 0,
+            matches: 0,
+            since_match: 0,
+            seen: 0,
+            unterminated: false,
+        }
+    }
+
+    /// How many lines or bytes have gone by, when counting to a `+N` offset.
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    /// Take the next line, returning it if it should be printed right away. `passes` says
+    /// whether it gets through the sieve: the last N lines are the last N that do, along
+    /// with their context, while bytes and `+N` offsets are measured on the input as it is.
+    pub fn push(&mut self, line: Line<'static>, passes: bool) -> Option<Line<'static>> {
+        self.take(line, passes, true)
+    }
+
+    /// Take the input's last line, which has no newline after it to count as a byte.
+    pub fn push_last(&mut self, line: Line<'static>, passes: bool) -> Option<Line<'static>> {
+        self.take(line, passes, false)
+    }
+
+    fn take(
+        &mut self,
+        mut line: Line<'static>,
+        passes: bool,
+        newline: bool,
+    ) -> Option<Line<'static>> {
+        let len = line.text().len() as u64 + u64::from(newline);
+        self.unterminated = !newline;
+
+        match (self.unit, self.count) {
+            (_, Count::Last(0)) => {}
+            (Unit::Lines, Count::Last(n)) => self.push_line(line, passes, n),
+            (Unit::Bytes, Count::Last(n)) => {
+                self.bytes += len;
+                self.held.push_back(Held::Line {
+                    line,
+                    matched: passes,
+                });
+
+                // Drop whole lines while that leaves enough, then cut the first one short.
+                while self.bytes > n {
+                    let last = self.unterminated && self.held.len() == 1;
+                    let Some(Held::Line { line: front, .. }) = self.held.front_mut() else {
+                        break;
+                    };
+                    let front_len = front.text().len() as u64 + u64::from(!last);
+                    if self.bytes - front_len >= n {
+                        self.held.pop_front();
+                        self.bytes -= front_len;
+                    } else {
+                        let cut = self.bytes - n;
+                        front.cut(cut as usize);
+                        self.bytes -= cut;
+                    }
+                }
+            }
+            (Unit::Lines, Count::From(n)) => {
+                self.seen += 1;
+                if self.seen >= n {
+                    return Some(line);
+                }
+            }
+            (Unit::Bytes, Count::From(n)) => {
+                let skip = n.saturating_sub(1).saturating_sub(self.seen);
+                self.seen += len;
+                if skip < len {
+                    line.cut(skip as usize);
+                    return Some(line);
+                }
+            }
+        }
+
+        None
+    }
+
+    // Keep the last `n` matching lines, and only those others that could be their context.
+    fn push_line(&mut self, line: Line<'static>, matched: bool, n: u64) {
+        let Context { before, after } = self.context;
+
+        if matched {
+            self.held.push_back(Held::Line { line, matched });
+            self.matches += 1;
+            self.since_match = 0;
+
+            if self.matches > n {
+                while let Some(held) = self.held.pop_front() {
+                    if let Held::Line { matched: true, .. } = held {
+                        self.matches -= 1;
+                        break;
+                    }
+                }
+                // What's left ahead of the next match only matters as its before-context.
+                let leading = self
+                    .held
+                    .iter()
+                    .take_while(|h| !matches!(h, Held::Line { matched: true, .. }))
+                    .count();
+                self.held.drain(..leading.saturating_sub(before));
+            }
+            return;
+        }
+
+        self.since_match += 1;
+        self.held.push_back(Held::Line { line, matched });
+
+        if self.matches == 0 {
+            // Nothing has matched yet, so only the last few lines can be context.
+            if self.held.len() > before {
+                self.held.pop_front();
+            }
+        } else if self.since_match > after + before {
+            // Past the after-context of the last match, only the last `before` lines are
+            // kept; what fell out of them is replaced by a gap.
+            let out = self.held.len() - 1 - before;
+            if self.since_match == after + before + 1 {
+                self.held[out] = Held::Gap;
+            } else {
+                self.held.remove(out);
+            }
+        }
+    }
+}
+
+impl IntoIterator for Tail {
+    /// A line to print, or None where lines were left out.
+    type Item = Option<Line<'static>>;
+    type IntoIter = Box<dyn Iterator<Item = Option<Line<'static>>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.held.into_iter().map(|held| match held {
+            Held::Line { line, .. } => Some(line),
+            Held::Gap => None,
+        }))
+    }
+}
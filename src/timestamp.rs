@@ -0,0 +1,316 @@
+use std::{fs::File, io, sync::LazyLock};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Datelike, FixedOffset, Local, Month, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta,
+    TimeZone, Utc,
+};
+use regex::{Captures, Regex};
+
+use crate::decode::Encoding;
+use crate::forward::FwdLines;
+
+// Timestamps recognised without --ts-format: ISO 8601 and its space separated variant,
+// access logs' `01/Jan/2024:10:00:00 +0000`, and syslog's year-less `Jan  1 10:00:00`.
+static ISO: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(\d{4})-(\d{2})-(\d{2})[T ](\d{2}):(\d{2})(?::(\d{2})(?:[.,](\d{1,9}))?)?(?: ?(Z|[+-]\d{2}:?\d{2})\b)?").unwrap()
+});
+static CLF: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(\d{2})/([A-Z][a-z]{2})/(\d{4}):(\d{2}):(\d{2}):(\d{2})(?: ([+-]\d{4}))?")
+        .unwrap()
+});
+static SYSLOG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b([A-Z][a-z]{2}) +(\d{1,2}) (\d{2}):(\d{2}):(\d{2})\b").unwrap()
+});
+static RELATIVE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\d+) *([a-z]+)(?: +ago)?$").unwrap());
+
+/// Finds the timestamps in lines, in the --ts-format if one was given or else in any of the
+/// layouts that are recognised by default.
+#[derive(Clone, Debug)]
+pub struct Stamps {
+    format: Option<String>,
+}
+
+impl Stamps {
+    pub fn new(format: Option<String>) -> Self {
+        Self { format }
+    }
+
+    /// The first timestamp in a line.
+    pub fn find(&self, line: &[u8]) -> Option<DateTime<Utc>> {
+        let line = std::str::from_utf8(line).ok()?;
+        match &self.format {
+            Some(format) => with_format(line, format),
+            None => detect(line),
+        }
+    }
+}
+
+/// A stretch of time given by --since and --until. Lines are placed in it by their
+/// timestamps; a line without one, like the rest of a stack trace, isn't held to it.
+#[derive(Clone, Debug)]
+pub struct Span {
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    stamps: Stamps,
+}
+
+impl Span {
+    pub fn new(since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>, stamps: Stamps) -> Self {
+        Self {
+            since,
+            until,
+            stamps,
+        }
+    }
+
+    /// Whether the line's timestamp falls within the span, or it has none.
+    pub fn contains(&self, line: &[u8]) -> bool {
+        self.stamps.find(line).is_none_or(|ts| {
+            self.since.is_none_or(|since| ts >= since) && self.until.is_none_or(|until| ts <= until)
+        })
+    }
+
+    /// Whether a line is stamped before --since, or None if it has no timestamp.
+    pub fn is_before(&self, line: &[u8]) -> Option<bool> {
+        let ts = self.stamps.find(line)?;
+        Some(self.since.is_some_and(|since| ts < since))
+    }
+
+    /// The byte range of the file up to `end` that holds the span: from the first line
+    /// stamped at or after --since to the first one stamped after --until. The file is
+    /// taken to be in time order and is bisected, so only a few blocks are read.
+    pub fn seek(&self, f: &mut File, end: u64, encoding: Encoding) -> io::Result<(u64, u64)> {
+        let start = match self.since {
+            Some(since) => self.search(f, 0, end, encoding, |ts| ts >= since)?,
+            None => 0,
+        };
+        let stop = match self.until {
+            Some(until) => self.search(f, start, end, encoding, |ts| ts > until)?,
+            None => end,
+        };
+        Ok((start, stop))
+    }
+
+    // Offset of the first stamped line at or after `lo` whose timestamp is `past` the point
+    // looked for, or `end` if there is none.
+    fn search(
+        &self,
+        f: &mut File,
+        lo: u64,
+        end: u64,
+        encoding: Encoding,
+        past: impl Fn(DateTime<Utc>) -> bool,
+    ) -> io::Result<u64> {
+        let (mut lo, mut hi, mut found) = (lo, end, end);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.probe(f, mid, hi, end, encoding)? {
+                Some((offset, ts)) if past(ts) => {
+                    found = offset;
+                    hi = mid;
+                }
+                Some((offset, _)) => lo = offset + 1,
+                None => hi = mid,
+            }
+        }
+        Ok(found)
+    }
+
+    // The first stamped line that starts at or after `pos` and before `stop`.
+    fn probe(
+        &self,
+        f: &mut File,
+        pos: u64,
+        stop: u64,
+        end: u64,
+        encoding: Encoding,
+    ) -> io::Result<Option<(u64, DateTime<Utc>)>> {
+        // Starting on the newline before `pos`, the first line read is the rest of the one
+        // `pos` is in, or empty if `pos` starts a line. Either way it's skipped.
+        let nl = encoding.newline_len() as u64;
+        let pos = encoding.align(pos);
+        let (start, skip) = if pos < nl { (0, 0) } else { (pos - nl, 1) };
+
+        for line in FwdLines::new(f, start, end, encoding)?.skip(skip) {
+            let (offset, line) = line?;
+            if offset >= stop {
+                break;
+            }
+            if let Some(ts) = self.stamps.find(&encoding.decode(&line)) {
+                return Ok(Some((offset, ts)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Parse --since and --until: a date and time like `2026-10-18T12:00`, a date, a time
+/// today, `now`, `today`, `yesterday`, or a time ago like `10 min ago`. Times without an
+/// offset are local.
+pub fn parse_when(s: &str) -> Result<DateTime<Utc>, String> {
+    let s = s.trim().to_ascii_lowercase();
+    let now = Utc::now();
+    let today = Local::now().date_naive();
+
+    if s == "now" {
+        return Ok(now);
+    }
+    if let Some(caps) = RELATIVE.captures(&s) {
+        let n: i64 = caps[1].parse().map_err(|_| "too large".to_string())?;
+        let unit = match &caps[2] {
+            "s" | "sec" | "secs" | "second" | "seconds" => 1,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+            "d" | "day" | "days" => 24 * 60 * 60,
+            "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+            other => return Err(format!("unknown unit '{}'", other)),
+        };
+        return n
+            .checked_mul(unit)
+            .and_then(TimeDelta::try_seconds)
+            .and_then(|ago| now.checked_sub_signed(ago))
+            .ok_or("too large".to_string());
+    }
+
+    // Midnight of a day, or a time today.
+    let day = match s.as_str() {
+        "today" => Some(today),
+        "yesterday" => today.pred_opt(),
+        _ => NaiveDate::parse_from_str(&s, "%Y-%m-%d").ok(),
+    };
+    let time = ["%H:%M", "%H:%M:%S"]
+        .into_iter()
+        .find_map(|format| NaiveTime::parse_from_str(&s, format).ok());
+    if let Some(ts) = day
+        .map(NaiveDateTime::from)
+        .or(time.map(|t| today.and_time(t)))
+    {
+        return local(ts).ok_or("not a valid local time".to_string());
+    }
+
+    // Upper case again, for the `T` and `Z` of ISO 8601.
+    detect(&s.to_ascii_uppercase())
+        .ok_or("expected a time like 2026-10-18T12:00, 12:00 or '10 min ago'".to_string())
+}
+
+/// Check a --ts-format string, which uses strftime's specifiers.
+pub fn parse_ts_format(s: &str) -> Result<String, String> {
+    if StrftimeItems::new(s).any(|item| matches!(item, Item::Error)) {
+        return Err("not a valid strftime format".to_string());
+    }
+    Ok(s.to_string())
+}
+
+// The leftmost timestamp in a known layout.
+fn detect(line: &str) -> Option<DateTime<Utc>> {
+    type Parse = fn(&Captures) -> Option<DateTime<Utc>>;
+    let layouts: [(&Regex, Parse); 3] = [(&ISO, iso), (&CLF, clf), (&SYSLOG, syslog)];
+
+    let (caps, parse) = layouts
+        .into_iter()
+        .filter_map(|(re, parse)| Some((re.captures(line)?, parse)))
+        .min_by_key(|(caps, _)| caps.get(0).map(|m| m.start()))?;
+    parse(&caps)
+}
+
+fn num(caps: &Captures, i: usize) -> Option<u32> {
+    caps.get(i)?.as_str().parse().ok()
+}
+
+fn iso(caps: &Captures) -> Option<DateTime<Utc>> {
+    let date = NaiveDate::from_ymd_opt(caps[1].parse().ok()?, num(caps, 2)?, num(caps, 3)?)?;
+    // Fractions of a second are padded out to nanoseconds.
+    let nanos = match caps.get(7) {
+        Some(frac) => format!("{:0<9}", frac.as_str()).parse().ok()?,
+        None => 0,
+    };
+    let time = NaiveTime::from_hms_nano_opt(
+        num(caps, 4)?,
+        num(caps, 5)?,
+        num(caps, 6).unwrap_or(0),
+        nanos,
+    )?;
+    at(date.and_time(time), caps.get(8).map(|m| m.as_str()))
+}
+
+fn clf(caps: &Captures) -> Option<DateTime<Utc>> {
+    let month = caps[2].parse::<Month>().ok()?.number_from_month();
+    let date = NaiveDate::from_ymd_opt(caps[3].parse().ok()?, month, num(caps, 1)?)?;
+    let time = NaiveTime::from_hms_opt(num(caps, 4)?, num(caps, 5)?, num(caps, 6)?)?;
+    at(date.and_time(time), caps.get(7).map(|m| m.as_str()))
+}
+
+// Syslog leaves out the year. It's this year's, unless that's in the future, as it is for
+// December's lines read in January.
+fn syslog(caps: &Captures) -> Option<DateTime<Utc>> {
+    let month = caps[1].parse::<Month>().ok()?.number_from_month();
+    let day = num(caps, 2)?;
+    let time = NaiveTime::from_hms_opt(num(caps, 3)?, num(caps, 4)?, num(caps, 5)?)?;
+
+    let now = Utc::now();
+    let year = Local::now().year();
+    let ts = local(NaiveDate::from_ymd_opt(year, month, day)?.and_time(time))?;
+    if ts > now + TimeDelta::days(1) {
+        local(NaiveDate::from_ymd_opt(year - 1, month, day)?.and_time(time))
+    } else {
+        Some(ts)
+    }
+}
+
+// The first place in the line where a timestamp in the given format can be read. A format
+// without a date is taken to be for today.
+fn with_format(line: &str, format: &str) -> Option<DateTime<Utc>> {
+    let starts = line
+        .char_indices()
+        .filter(|&(i, c)| {
+            c.is_alphanumeric()
+                && !line[..i]
+                    .chars()
+                    .next_back()
+                    .is_some_and(char::is_alphanumeric)
+        })
+        .map(|(i, _)| i);
+
+    for i in starts {
+        let rest = &line[i..];
+        if let Ok((ts, _)) = DateTime::<FixedOffset>::parse_and_remainder(rest, format) {
+            return Some(ts.to_utc());
+        }
+        if let Ok((ts, _)) = NaiveDateTime::parse_and_remainder(rest, format) {
+            return local(ts);
+        }
+        if let Ok((time, _)) = NaiveTime::parse_and_remainder(rest, format) {
+            return local(Local::now().date_naive().and_time(time));
+        }
+    }
+    None
+}
+
+// A time with the offset written next to it, like `Z`, `+02:00` or `-0700`, or else in the
+// local time zone.
+fn at(ts: NaiveDateTime, offset: Option<&str>) -> Option<DateTime<Utc>> {
+    let Some(offset) = offset else {
+        return local(ts);
+    };
+    let secs = if offset == "Z" {
+        0
+    } else {
+        let digits = offset[1..].replace(':', "");
+        let hours: i32 = digits.get(..2)?.parse().ok()?;
+        let minutes: i32 = digits.get(2..)?.parse().ok()?;
+        let secs = hours * 3600 + minutes * 60;
+        if offset.starts_with('-') {
+            -secs
+        } else {
+            secs
+        }
+    };
+    let offset = FixedOffset::east_opt(secs)?;
+    Some(offset.from_local_datetime(&ts).single()?.to_utc())
+}
+
+fn local(ts: NaiveDateTime) -> Option<DateTime<Utc>> {
+    Some(Local.from_local_datetime(&ts).earliest()?.to_utc())
+}
@@ -0,0 +1,687 @@
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::PathBuf,
+    sync::mpsc::{Receiver, TryRecvError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::{
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+    DefaultTerminal, Frame,
+};
+use regex::Regex;
+
+use crate::level::Level;
+use crate::pager::SCROLLBACK;
+use crate::printer::{Captured, PREFIX_COLORS};
+use crate::sieve::{Combinator, Sieve};
+
+// How long to wait for a key before drawing whatever lines came in meanwhile.
+const TICK: Duration = Duration::from_millis(100);
+
+const HELP: &str =
+    "q quit  space pause  ↑ ↓ PgUp PgDn g G scroll  s sieve  x exclude  / search  n N next/prev";
+
+/// What the TUI starts with, from the command line.
+pub struct Options {
+    pub names: Vec<String>,
+    // The path of each file, for the status bar; None for standard input.
+    pub paths: Vec<Option<PathBuf>>,
+    pub sieve: Vec<String>,
+    pub exclude: Vec<String>,
+    pub regex: bool,
+    pub groups: bool,
+    pub combinator: Combinator,
+}
+
+// A line in the scrollback. Lines are numbered in the order they came in, so a place in the
+// scrollback stays put as old lines are dropped and the sieve is changed.
+struct Entry {
+    seq: u64,
+    idx: usize,
+    text: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Prompt {
+    Sieve,
+    Exclude,
+    Search,
+}
+
+struct App {
+    options: Options,
+    lines: VecDeque<Entry>,
+    // The number the next line gets.
+    next: u64,
+    sieve: Option<Sieve>,
+    // What was searched for, as typed, and compiled.
+    search: Option<(String, Regex)>,
+    // The lines that get through the sieve, by number.
+    shown: VecDeque<u64>,
+    // The first line on screen while paused, or None while the end is followed.
+    top: Option<u64>,
+    // Lines that got through the sieve while paused.
+    unseen: u64,
+    // Whether more lines can still come in.
+    live: bool,
+    prompt: Option<(Prompt, String)>,
+    message: Option<String>,
+    // Problems met while following, written out once the terminal is restored.
+    warnings: Vec<String>,
+    // The rows lines were shown in at the last draw.
+    height: usize,
+}
+
+/// Show the lines sent by the printer full-screen until the user quits, following the files
+/// on another thread with `follow`, which returns whether it was stopped by a signal; that
+/// ends the TUI too. The sieve is applied here rather than by the printer, so that changing
+/// it brings back lines it left out before.
+pub fn run(
+    options: Options,
+    lines: Receiver<Captured>,
+    follow: Option<impl FnOnce() -> bool + Send + 'static>,
+) -> io::Result<()> {
+    let follow = follow.map(thread::spawn);
+
+    let mut app = App::new(options);
+    let mut terminal = ratatui::try_init()?;
+    let res = app.run(&mut terminal, &lines, follow);
+    ratatui::restore();
+    // Only shown briefly on screen, so they're kept for the terminal too.
+    for warning in &app.warnings {
+        eprintln!("trunk: {}", warning);
+    }
+    res
+}
+
+impl App {
+    fn new(options: Options) -> Self {
+        let sieve = compile(&options, &options.sieve, &options.exclude)
+            .ok()
+            .flatten();
+        Self {
+            options,
+            lines: VecDeque::new(),
+            next: 0,
+            sieve,
+            search: None,
+            shown: VecDeque::new(),
+            top: None,
+            unseen: 0,
+            live: true,
+            prompt: None,
+            message: None,
+            warnings: Vec::new(),
+            height: 0,
+        }
+    }
+
+    fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        lines: &Receiver<Captured>,
+        mut follow: Option<JoinHandle<bool>>,
+    ) -> io::Result<()> {
+        loop {
+            if follow.as_ref().is_some_and(JoinHandle::is_finished) {
+                let interrupted = follow.take().unwrap().join().unwrap_or(false);
+                if interrupted {
+                    return Ok(());
+                }
+            }
+
+            loop {
+                match lines.try_recv() {
+                    Ok(Captured::Line(idx, text)) => self.push(idx, &text),
+                    Ok(Captured::Warning(text)) => {
+                        self.message = Some(text.clone());
+                        self.warnings.push(text);
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        self.live = false;
+                        break;
+                    }
+                }
+            }
+
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if event::poll(TICK)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press && self.key(key) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    // Add a line to the scrollback.
+    fn push(&mut self, idx: usize, text: &str) {
+        let entry = Entry {
+            seq: self.next,
+            idx,
+            // Tabs would throw the terminal's columns off.
+            text: text.replace('\t', "    "),
+        };
+        self.next += 1;
+
+        if self.passes(&entry) {
+            self.shown.push_back(entry.seq);
+            if self.top.is_some() {
+                self.unseen += 1;
+            }
+        }
+        self.lines.push_back(entry);
+
+        while self.lines.len() > SCROLLBACK {
+            self.lines.pop_front();
+        }
+        let first = self.lines.front().map_or(self.next, |e| e.seq);
+        while self.shown.front().is_some_and(|&seq| seq < first) {
+            self.shown.pop_front();
+        }
+    }
+
+    fn passes(&self, entry: &Entry) -> bool {
+        self.sieve
+            .as_ref()
+            .is_none_or(|sieve| sieve.is_match(entry.text.as_bytes()))
+    }
+
+    fn entry(&self, seq: u64) -> &Entry {
+        &self.lines[(seq - self.lines[0].seq) as usize]
+    }
+
+    // Sieve the whole scrollback again, after the sieve has changed.
+    fn refilter(&mut self) {
+        self.shown = self
+            .lines
+            .iter()
+            .filter(|e| self.passes(e))
+            .map(|e| e.seq)
+            .collect();
+        self.unseen = 0;
+    }
+
+    // The position in `shown` of the first line on screen.
+    fn start(&self) -> usize {
+        match self.top {
+            None => self.shown.len().saturating_sub(self.height),
+            Some(top) => self.shown.partition_point(|&seq| seq < top),
+        }
+    }
+
+    // The furthest down the screen can be scrolled with it still full.
+    fn bottom(&self) -> usize {
+        self.shown.len().saturating_sub(self.height)
+    }
+
+    // Pause with the line at `pos` at the top of the screen.
+    fn scroll_to(&mut self, pos: usize) {
+        let pos = pos.min(self.shown.len().saturating_sub(1));
+        if self.top.is_none() {
+            self.unseen = 0;
+        }
+        self.top = Some(self.shown.get(pos).copied().unwrap_or(self.next));
+    }
+
+    fn resume(&mut self) {
+        self.top = None;
+        self.unseen = 0;
+    }
+
+    fn toggle_pause(&mut self) {
+        match self.top {
+            Some(_) => self.resume(),
+            None => self.scroll_to(self.start()),
+        }
+    }
+
+    // Go to the next line from `from` matching the search, going down or up.
+    fn find(&mut self, from: usize, down: bool) {
+        let Some((text, search)) = &self.search else {
+            self.message = Some("nothing to search for; / starts a search".to_string());
+            return;
+        };
+
+        let from = from.min(self.shown.len());
+        let hit = |seq: &u64| search.is_match(&self.entry(*seq).text);
+        let found = if down {
+            self.shown.range(from..).position(hit).map(|pos| from + pos)
+        } else {
+            self.shown.range(..from).rposition(hit)
+        };
+
+        match found {
+            Some(pos) => self.scroll_to(pos),
+            None => self.message = Some(format!("not found: {}", text)),
+        }
+    }
+
+    // Handle a key press. Returns whether to quit.
+    fn key(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return true;
+        }
+
+        if let Some((_, text)) = &mut self.prompt {
+            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Char('u') if ctrl => text.clear(),
+                KeyCode::Char(c) if !ctrl => text.push(c),
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Esc => self.prompt = None,
+                KeyCode::Enter => {
+                    let (prompt, text) = self.prompt.take().unwrap();
+                    self.apply(prompt, text);
+                }
+                _ => {}
+            }
+            return false;
+        }
+
+        self.message = None;
+        let start = self.start();
+        let page = self.height.max(1);
+        let bottom = self.bottom();
+        let down = |by: usize| (start + by).min(bottom).max(start);
+        match key.code {
+            KeyCode::Char('q') => return true,
+            KeyCode::Char(' ') | KeyCode::Char('p') => self.toggle_pause(),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_to(start.saturating_sub(1)),
+            KeyCode::PageUp => self.scroll_to(start.saturating_sub(page)),
+            KeyCode::Home | KeyCode::Char('g') => self.scroll_to(0),
+            // Scrolling down doesn't pause, so it does nothing while the end is followed.
+            KeyCode::Down | KeyCode::Char('j') if self.top.is_some() => self.scroll_to(down(1)),
+            KeyCode::PageDown if self.top.is_some() => self.scroll_to(down(page)),
+            KeyCode::End | KeyCode::Char('G') => self.resume(),
+            KeyCode::Char('s') => {
+                self.prompt = Some((Prompt::Sieve, self.options.sieve.join("|")));
+            }
+            KeyCode::Char('x') => {
+                self.prompt = Some((Prompt::Exclude, self.options.exclude.join("|")));
+            }
+            KeyCode::Char('/') => self.prompt = Some((Prompt::Search, String::new())),
+            KeyCode::Char('n') => self.find(start + 1, true),
+            KeyCode::Char('N') => self.find(start, false),
+            _ => {}
+        }
+        false
+    }
+
+    // Act on what was typed at a prompt.
+    fn apply(&mut self, prompt: Prompt, text: String) {
+        // Literal phrases are separated by `|`, while a regex is taken whole.
+        let terms: Vec<String> = if self.options.regex {
+            Some(text.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .into_iter()
+                .collect()
+        } else {
+            text.split('|')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(String::from)
+                .collect()
+        };
+
+        match prompt {
+            Prompt::Sieve | Prompt::Exclude => {
+                let compiled = if prompt == Prompt::Sieve {
+                    compile(&self.options, &terms, &self.options.exclude)
+                } else {
+                    compile(&self.options, &self.options.sieve, &terms)
+                };
+                match compiled {
+                    Ok(sieve) => {
+                        if prompt == Prompt::Sieve {
+                            self.options.sieve = terms;
+                        } else {
+                            self.options.exclude = terms;
+                        }
+                        self.sieve = sieve;
+                        self.refilter();
+                    }
+                    Err(e) => self.message = Some(format!("invalid sieve: {}", e)),
+                }
+            }
+            Prompt::Search if text.is_empty() => self.search = None,
+            Prompt::Search => {
+                let pattern = if self.options.regex {
+                    text.clone()
+                } else {
+                    regex::escape(&text)
+                };
+                match Regex::new(&pattern) {
+                    Ok(re) => {
+                        self.search = Some((text, re));
+                        // While following, the latest match is the one wanted.
+                        match self.top {
+                            None => self.find(self.shown.len(), false),
+                            Some(_) => self.find(self.start(), true),
+                        }
+                    }
+                    Err(e) => self.message = Some(format!("invalid search: {}", e)),
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [body, status, bottom] = Layout::vertical([
+            Constraint::Min(0),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        self.height = body.height as usize;
+
+        let start = self.start();
+        let end = (start + self.height).min(self.shown.len());
+        let rows: Vec<Line> = self
+            .shown
+            .range(start..end)
+            .map(|&seq| self.render(self.entry(seq)))
+            .collect();
+        frame.render_widget(Paragraph::new(rows), body);
+
+        let reversed = Style::new().add_modifier(Modifier::REVERSED);
+        frame.render_widget(Paragraph::new(self.status()).style(reversed), status);
+
+        match &self.prompt {
+            Some((prompt, text)) => {
+                let label = match prompt {
+                    Prompt::Sieve => "sieve: ",
+                    Prompt::Exclude => "exclude: ",
+                    Prompt::Search => "/",
+                };
+                frame.render_widget(Paragraph::new(format!("{}{}", label, text)), bottom);
+                let x = bottom.x + (label.chars().count() + text.chars().count()) as u16;
+                frame.set_cursor_position((x.min(bottom.right().saturating_sub(1)), bottom.y));
+            }
+            None => {
+                let (text, style) = match &self.message {
+                    Some(message) => (message.as_str(), Style::new().fg(Color::Yellow)),
+                    None => (HELP, Style::new().fg(Color::DarkGray)),
+                };
+                frame.render_widget(Paragraph::new(text).style(style), bottom);
+            }
+        }
+    }
+
+    // A line coloured by its level, with the sieve's matches highlighted as they are when
+    // printed, and the search's shown reversed.
+    fn render(&self, entry: &Entry) -> Line<'static> {
+        let text = &entry.text;
+        let base = Level::detect(text.as_bytes())
+            .and_then(Level::line_color)
+            .map_or(Style::new(), |c| Style::new().fg(color(c)));
+
+        let mut styles = vec![base; text.len()];
+        for span in self.sieve.iter().flat_map(|s| s.spans(text.as_bytes())) {
+            styles[span.start..span.end].fill(Style::new().fg(color(span.color)));
+        }
+        for m in self.search.iter().flat_map(|(_, re)| re.find_iter(text)) {
+            for style in &mut styles[m.range()] {
+                *style = style.add_modifier(Modifier::REVERSED);
+            }
+        }
+
+        let mut spans = Vec::new();
+        if self.options.names.len() > 1 {
+            let prefix = format!("[{}] ", self.options.names[entry.idx]);
+            let c = PREFIX_COLORS[entry.idx % PREFIX_COLORS.len()];
+            spans.push(Span::styled(prefix, Style::new().fg(color(c))));
+        }
+        let mut from = 0;
+        for (i, _) in text.char_indices() {
+            if styles[i] != styles[from] {
+                spans.push(Span::styled(text[from..i].to_string(), styles[from]));
+                from = i;
+            }
+        }
+        if from < text.len() {
+            spans.push(Span::styled(text[from..].to_string(), styles[from]));
+        }
+        Line::from(spans)
+    }
+
+    fn status(&self) -> String {
+        let mut parts = Vec::new();
+        parts.push(match self.options.names.as_slice() {
+            [name] => name.clone(),
+            names => format!("{} files", names.len()),
+        });
+
+        let paths: Vec<_> = self.options.paths.iter().flatten().collect();
+        if !paths.is_empty() {
+            let size = paths
+                .iter()
+                .filter_map(|p| fs::metadata(p).ok())
+                .map(|m| m.len())
+                .sum();
+            parts.push(human_size(size));
+        }
+
+        parts.push(format!(
+            "{} of {} lines",
+            self.shown.len(),
+            self.lines.len()
+        ));
+        if !self.options.sieve.is_empty() {
+            parts.push(format!("sieve {}", self.options.sieve.join("|")));
+        }
+        if !self.options.exclude.is_empty() {
+            parts.push(format!("exclude {}", self.options.exclude.join("|")));
+        }
+        if let Some((text, _)) = &self.search {
+            parts.push(format!("/{}", text));
+        }
+
+        parts.push(match (self.top, self.live) {
+            (Some(_), _) => format!("PAUSED +{}", self.unseen),
+            (None, true) => "FOLLOWING".to_string(),
+            (None, false) => "END".to_string(),
+        });
+        format!(" {}", parts.join(" | "))
+    }
+}
+
+// The sieve for the given terms and excludes, or None if there are neither.
+fn compile(
+    options: &Options,
+    sieve: &[String],
+    exclude: &[String],
+) -> Result<Option<Sieve>, regex::Error> {
+    if sieve.is_empty() && exclude.is_empty() {
+        return Ok(None);
+    }
+    Sieve::new(
+        sieve,
+        exclude,
+        options.regex,
+        options.groups,
+        options.combinator,
+    )
+    .map(Some)
+}
+
+// A size in bytes, in the largest binary unit it makes at least one of.
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+// The terminal colour for one of `colored`'s.
+fn color(c: colored::Color) -> Color {
+    match c {
+        colored::Color::Black => Color::Black,
+        colored::Color::Red => Color::Red,
+        colored::Color::Green => Color::Green,
+        colored::Color::Yellow => Color::Yellow,
+        colored::Color::Blue => Color::Blue,
+        colored::Color::Magenta => Color::Magenta,
+        colored::Color::Cyan => Color::Cyan,
+        colored::Color::White => Color::Gray,
+        colored::Color::BrightBlack => Color::DarkGray,
+        colored::Color::BrightRed => Color::LightRed,
+        colored::Color::BrightGreen => Color::LightGreen,
+        colored::Color::BrightYellow => Color::LightYellow,
+        colored::Color::BrightBlue => Color::LightBlue,
+        colored::Color::BrightMagenta => Color::LightMagenta,
+        colored::Color::BrightCyan => Color::LightCyan,
+        colored::Color::BrightWhite => Color::White,
+        colored::Color::TrueColor { r, g, b } => Color::Rgb(r, g, b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(sieve: &[&str]) -> App {
+        let mut app = App::new(Options {
+            names: vec!["app.log".to_string()],
+            paths: vec![None],
+            sieve: sieve.iter().map(|s| s.to_string()).collect(),
+            exclude: Vec::new(),
+            regex: false,
+            groups: false,
+            combinator: Combinator::Any,
+        });
+        app.height = 3;
+        app
+    }
+
+    fn press(app: &mut App, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\x1b' => KeyCode::Esc,
+                c => KeyCode::Char(c),
+            };
+            app.key(KeyEvent::new(code, KeyModifiers::NONE));
+        }
+    }
+
+    fn shown(app: &App) -> Vec<&str> {
+        app.shown
+            .iter()
+            .map(|&seq| app.entry(seq).text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn lines_are_counted_while_paused() {
+        let mut app = app(&["error"]);
+        for text in ["error 1", "info 2", "error 3"] {
+            app.push(0, text);
+        }
+        assert_eq!(app.unseen, 0);
+
+        press(&mut app, " ");
+        assert!(app.top.is_some());
+        for text in ["error 4", "info 5", "error 6"] {
+            app.push(0, text);
+        }
+        // Only what gets through the sieve is new to see.
+        assert_eq!(app.unseen, 2);
+        assert!(app.status().contains("PAUSED +2"));
+
+        press(&mut app, " ");
+        assert_eq!(app.top, None);
+        assert_eq!(app.unseen, 0);
+        assert_eq!(app.start(), 1);
+    }
+
+    #[test]
+    fn scrollback_drops_the_oldest_lines() {
+        let mut app = app(&["even"]);
+        for i in 0..SCROLLBACK + 10 {
+            let parity = if i % 2 == 0 { "even" } else { "odd" };
+            app.push(0, &format!("{} {}", parity, i));
+        }
+
+        assert_eq!(app.lines.len(), SCROLLBACK);
+        assert_eq!(app.lines[0].text, "even 10");
+        assert_eq!(app.shown.len(), SCROLLBACK / 2);
+        assert_eq!(app.entry(app.shown[0]).text, "even 10");
+    }
+
+    #[test]
+    fn sieve_is_edited_and_reapplied() {
+        let mut app = app(&[]);
+        for text in ["GET /a", "POST /b", "GET /c", "DELETE /d"] {
+            app.push(0, text);
+        }
+        assert_eq!(shown(&app).len(), 4);
+
+        press(&mut app, "sGET | DELETE\n");
+        assert_eq!(app.options.sieve, ["GET", "DELETE"]);
+        assert_eq!(shown(&app), ["GET /a", "GET /c", "DELETE /d"]);
+
+        // Ctrl-U clears what's there, and Esc leaves the sieve as it was.
+        press(&mut app, "s");
+        app.key(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL));
+        assert_eq!(app.prompt, Some((Prompt::Sieve, String::new())));
+        press(&mut app, "POST\x1b");
+        assert_eq!(shown(&app).len(), 3);
+
+        press(&mut app, "x/c\n");
+        assert_eq!(shown(&app), ["GET /a", "DELETE /d"]);
+
+        // Clearing both brings back every line.
+        press(&mut app, "s");
+        app.key(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL));
+        press(&mut app, "\nx");
+        app.key(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL));
+        press(&mut app, "\n");
+        assert!(app.sieve.is_none());
+        assert_eq!(shown(&app).len(), 4);
+    }
+
+    #[test]
+    fn search_goes_to_matches_and_says_when_there_are_none() {
+        let mut app = app(&[]);
+        for i in 0..10 {
+            let text = if i % 4 == 1 { "match" } else { "line" };
+            app.push(0, &format!("{} {}", text, i));
+        }
+
+        // While following, the search pauses on the latest match.
+        press(&mut app, "/match\n");
+        assert_eq!(app.start(), 9);
+        press(&mut app, "N");
+        assert_eq!(app.start(), 5);
+        press(&mut app, "N");
+        assert_eq!(app.start(), 1);
+        press(&mut app, "N");
+        assert_eq!(app.message.as_deref(), Some("not found: match"));
+        assert_eq!(app.start(), 1);
+        press(&mut app, "n");
+        assert_eq!(app.start(), 5);
+
+        press(&mut app, "/nothing\n");
+        assert_eq!(app.message.as_deref(), Some("not found: nothing"));
+    }
+}
@@ -1,7 +1,19 @@
-use std::process::Command;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+    thread,
+    time::Duration,
+};
+
+use flate2::{write::GzEncoder, Compression};
+
+// Time given to the watcher to settle before and after writing to a followed file.
+const SETTLE: Duration = Duration::from_millis(500);
 
 fn get_base() -> String {
-    "".to_string()
+    env!("CARGO_TARGET_TMPDIR").to_string()
 }
 
 struct Kelvin {
@@ -9,22 +21,1182 @@ struct Kelvin {
 }
 
 impl Kelvin {
-    fn new() -> Self {
-        Kelvin { base: get_base() }
+    fn new(name: &str) -> Self {
+        let base = format!("{}/{}", get_base(), name);
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(&base).unwrap();
+        Kelvin { base }
     }
 
-    // fn read_output() -> Self {
-    //     Command::new(program)
-    // }
+    fn file(&self, name: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+        let path = PathBuf::from(&self.base).join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn append(path: &PathBuf, contents: &str) {
+        let mut f = OpenOptions::new().append(true).open(path).unwrap();
+        f.write_all(contents.as_bytes()).unwrap();
+    }
+
+    fn command(args: &[&str]) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_trunk"));
+        cmd.args(args)
+            .env_remove("CLICOLOR_FORCE")
+            .stdin(Stdio::null());
+        cmd
+    }
+
+    fn run(args: &[&str]) -> Output {
+        Kelvin::command(args).output().unwrap()
+    }
+
+    // Run trunk to completion with the file at `input` piped to its stdin.
+    fn pipe(args: &[&str], input: &PathBuf) -> Output {
+        Kelvin::command(args)
+            .stdin(fs::File::open(input).unwrap())
+            .output()
+            .unwrap()
+    }
+
+    // Run trunk in follow mode, call `action` once it is watching, then stop it and return stdout.
+    fn follow(args: &[&str], colour: bool, action: impl FnOnce()) -> String {
+        let mut cmd = Kelvin::command(args);
+        if colour {
+            cmd.env("CLICOLOR_FORCE", "1");
+        }
+
+        let child = cmd
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        thread::sleep(SETTLE);
+        action();
+        thread::sleep(SETTLE);
+
+        let mut child = child;
+        child.kill().unwrap();
+        String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap()
+    }
 }
 
+// Test for -n
 #[test]
-fn it_works() {
-    assert_eq!(4, 2 + 2);
+fn tails_last_lines() {
+    let k = Kelvin::new("tails_last_lines");
+    let path = k.file("app.log", "one\ntwo\nthree\nfour\n");
+    let file = path.to_str().unwrap();
+
+    let out = Kelvin::run(&["-n", "2", file]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "three\nfour\n");
+
+    let out = Kelvin::run(&["-n", "10", file]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "one\ntwo\nthree\nfour\n"
+    );
 }
 
-// Test for -n
+#[test]
+fn tails_lines_longer_than_a_block() {
+    let k = Kelvin::new("tails_lines_longer_than_a_block");
+    let long = "x".repeat(300 * 1024);
+    let path = k.file("app.log", format!("first\n{}\nlast", long));
+
+    let out = Kelvin::run(&["-n", "2", path.to_str().unwrap()]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        format!("{}\nlast\n", long)
+    );
+}
+
+#[test]
+fn tails_unterminated_line_longer_than_a_block() {
+    let k = Kelvin::new("tails_unterminated_line_longer_than_a_block");
+    let long = "x".repeat(70 * 1024);
+    let path = k.file("app.log", format!("first\n{}", long));
+    let file = path.to_str().unwrap();
+
+    for args in [
+        &["-n", "2", file][..],
+        &["-n", "+1", file],
+        &["-c", "+1", file],
+    ] {
+        let out = Kelvin::run(args);
+        assert!(out.status.success());
+        assert_eq!(
+            String::from_utf8(out.stdout).unwrap(),
+            format!("first\n{}\n", long)
+        );
+    }
+}
+
+#[test]
+fn bytes_and_offsets() {
+    let k = Kelvin::new("bytes_and_offsets");
+    let input = k.file("app.log", "one\ntwo\nthree\nfour\n");
+    let file = input.to_str().unwrap();
+
+    let cases: [(&[&str], &str); 5] = [
+        (&["-c", "6"], "\nfour\n"),
+        (&["-c", "+15"], "four\n"),
+        (&["-c", "+12"], "ee\nfour\n"),
+        (&["-n", "+3"], "three\nfour\n"),
+        (&["-n", "+0"], "one\ntwo\nthree\nfour\n"),
+    ];
+    for (args, expected) in cases {
+        let out = Kelvin::run(&[args, &[file]].concat());
+        assert_eq!(
+            String::from_utf8(out.stdout).unwrap(),
+            expected,
+            "{:?}",
+            args
+        );
+
+        let out = Kelvin::pipe(args, &input);
+        assert_eq!(
+            String::from_utf8(out.stdout).unwrap(),
+            expected,
+            "{:?} from stdin",
+            args
+        );
+    }
+
+    // Without a newline at the end there's no last byte for it.
+    let input = k.file("unterminated.log", "one\ntwo\nthree");
+    let file = input.to_str().unwrap();
+    let cases: [(&[&str], &str); 4] = [
+        (&["-c", "3"], "ree\n"),
+        (&["-c", "7"], "o\nthree\n"),
+        (&["-c", "+13"], "e\n"),
+        (&["-c", "+14"], ""),
+    ];
+    for (args, expected) in cases {
+        let out = Kelvin::run(&[args, &[file]].concat());
+        assert_eq!(
+            String::from_utf8(out.stdout).unwrap(),
+            expected,
+            "{:?}",
+            args
+        );
+
+        let out = Kelvin::pipe(args, &input);
+        assert_eq!(
+            String::from_utf8(out.stdout).unwrap(),
+            expected,
+            "{:?} from stdin",
+            args
+        );
+    }
+}
+
+#[test]
+fn offsets_span_many_blocks() {
+    let k = Kelvin::new("offsets_span_many_blocks");
+    let lines: String = (0..100_000).map(|i| format!("line {}\n", i)).collect();
+    let path = k.file("app.log", &lines);
+
+    let out = Kelvin::run(&["-n", "+99998", path.to_str().unwrap()]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "line 99997\nline 99998\nline 99999\n"
+    );
+
+    let out = Kelvin::run(&["-c", "1K", path.to_str().unwrap()]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        &lines[lines.len() - 1024..]
+    );
+}
+
+#[test]
+fn invalid_numbers_are_cli_errors() {
+    let cases: [(&[&str], &str); 5] = [
+        (&["-n", "5x"], "unknown suffix 'x'"),
+        (&["-n", "abc"], "not a number"),
+        (&["-c", "99999999999G"], "too large"),
+        (&["--poll=0"], "must be more than 0"),
+        (&["--partial-timeout", "soon"], "not a number of seconds"),
+    ];
+    for (args, message) in cases {
+        let out = Kelvin::run(args);
+        assert_eq!(out.status.code(), Some(2), "{:?}", args);
+        assert!(
+            String::from_utf8(out.stderr).unwrap().contains(message),
+            "{:?}",
+            args
+        );
+    }
+}
+
+#[test]
+fn unreadable_file_is_reported_and_skipped() {
+    let k = Kelvin::new("unreadable_file_is_reported_and_skipped");
+    let dir = PathBuf::from(&k.base).join("dir");
+    fs::create_dir(&dir).unwrap();
+    let path = k.file("app.log", "one\n");
+
+    let out = Kelvin::run(&["-q", dir.to_str().unwrap(), path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "one\n");
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("cannot read"));
+}
+
+#[test]
+fn stdin_tails_last_lines() {
+    let k = Kelvin::new("stdin_tails_last_lines");
+    let input = k.file("input.log", "one\ntwo\nthree\nfour\nfive\n");
+
+    let out = Kelvin::pipe(&["-n", "2"], &input);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "four\nfive\n");
+
+    let out = Kelvin::pipe(&["-n", "10"], &input);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "one\ntwo\nthree\nfour\nfive\n"
+    );
+
+    let out = Kelvin::pipe(&["-n", "0"], &input);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+}
+
+#[test]
+fn stdin_tail_keeps_unterminated_last_line() {
+    let k = Kelvin::new("stdin_tail_keeps_unterminated_last_line");
+    let input = k.file("input.log", "a\n\nb\nc");
+
+    let out = Kelvin::pipe(&["-n", "3"], &input);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "\nb\nc\n");
+}
+
+#[test]
+fn stdin_tail_of_large_input() {
+    let k = Kelvin::new("stdin_tail_of_large_input");
+    let lines: String = (0..200_000).map(|i| format!("line {}\n", i)).collect();
+    let input = k.file("input.log", lines);
+
+    let out = Kelvin::pipe(&["-n", "3"], &input);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "line 199997\nline 199998\nline 199999\n"
+    );
+}
+
+#[test]
+fn stdin_tail_is_sieved() {
+    let k = Kelvin::new("stdin_tail_is_sieved");
+    let input = k.file(
+        "input.log",
+        "ERROR one\ninfo\nERROR two\nwarn\nERROR three\ninfo\n",
+    );
+
+    let out = Kelvin::pipe(&["-n", "2", "-s", "ERROR"], &input);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "ERROR two\nERROR three\n"
+    );
+}
 
 // Test for -f
 
 // Test for -s
+#[test]
+fn sieve_highlights_literal() {
+    let k = Kelvin::new("sieve_highlights_literal");
+    let path = k.file("app.log", "");
+
+    let out = Kelvin::follow(&["-s", "a.c", path.to_str().unwrap()], true, || {
+        Kelvin::append(&path, "abc\nxa.cx\n");
+    });
+    assert_eq!(out, "x\x1b[31ma.c\x1b[0mx\n");
+}
+
+#[test]
+fn sieve_regex_highlights_groups() {
+    let k = Kelvin::new("sieve_regex_highlights_groups");
+    let path = k.file("app.log", "");
+
+    let args = [
+        "-E",
+        "--groups",
+        "-s",
+        r"code=(\d+)",
+        path.to_str().unwrap(),
+    ];
+    let out = Kelvin::follow(&args, true, || {
+        Kelvin::append(&path, "ok\nerr code=42 done\n");
+    });
+    assert_eq!(out, "err \x1b[31mcode=\x1b[0m\x1b[33m42\x1b[0m done\n");
+}
+
+#[test]
+fn invalid_regex_is_cli_error() {
+    let k = Kelvin::new("invalid_regex_is_cli_error");
+    let path = k.file("app.log", "");
+
+    let out = Kelvin::run(&["-E", "-s", "(unclosed", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("invalid sieve"));
+}
+
+#[test]
+fn sieve_any_with_exclude() {
+    let k = Kelvin::new("sieve_any_with_exclude");
+    let path = k.file("app.log", "");
+
+    let file = path.to_str().unwrap();
+    let args = ["-s", "ERROR", "-s", "WARN", "-v", "healthcheck", file];
+    let out = Kelvin::follow(&args, false, || {
+        Kelvin::append(
+            &path,
+            "INFO up\nERROR disk\nWARN healthcheck slow\nWARN queue\n",
+        );
+    });
+    assert_eq!(out, "ERROR disk\nWARN queue\n");
+}
+
+#[test]
+fn sieve_all_colours_each_term() {
+    let k = Kelvin::new("sieve_all_colours_each_term");
+    let path = k.file("app.log", "");
+
+    let file = path.to_str().unwrap();
+    let args = ["--all", "-s", "db", "-s", "timeout", file];
+    let out = Kelvin::follow(&args, true, || {
+        Kelvin::append(&path, "db ok\ndb timeout\n");
+    });
+    assert_eq!(out, "\x1b[31mdb\x1b[0m \x1b[34mtimeout\x1b[0m\n");
+}
+
+#[test]
+fn sieve_applies_to_backlog() {
+    let k = Kelvin::new("sieve_applies_to_backlog");
+    let mut contents = String::new();
+    for i in 0..20000 {
+        contents.push_str(&format!(
+            "{} {}\n",
+            if i % 7 == 0 { "ERROR" } else { "INFO" },
+            i
+        ));
+    }
+    let path = k.file("app.log", &contents);
+
+    let file = path.to_str().unwrap();
+    let out = Kelvin::follow(&["-s", "ERROR", "-n", "3", file], false, || {});
+    assert_eq!(out, "ERROR 19985\nERROR 19992\nERROR 19999\n");
+}
+
+#[test]
+fn context_around_matches() {
+    let k = Kelvin::new("context_around_matches");
+    let contents = "a\nb\nERR 1\nc\nd\ne\nf\nERR 2\ng\nERR 3\nh\ni\n";
+    let path = k.file("app.log", contents);
+    let expected = "b\nERR 1\nc\n--\nf\nERR 2\ng\nERR 3\nh\n";
+
+    let out = Kelvin::follow(
+        &["-s", "ERR", "-C", "1", "-n", "10", path.to_str().unwrap()],
+        false,
+        || {},
+    );
+    assert_eq!(out, expected);
+
+    let input = k.file("input.log", contents);
+    let out = Kelvin::pipe(&["-s", "ERR", "-C", "1", "-n", "10"], &input);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), expected);
+}
+
+#[test]
+fn after_context_carries_into_follow() {
+    let k = Kelvin::new("after_context_carries_into_follow");
+    let path = k.file("app.log", "info\nERROR boom\n");
+
+    let out = Kelvin::follow(
+        &["-s", "ERROR", "-A", "2", path.to_str().unwrap()],
+        false,
+        || {
+            Kelvin::append(&path, "  at one\n  at two\n  at three\nERROR again\n");
+        },
+    );
+    assert_eq!(out, "ERROR boom\n  at one\n  at two\n--\nERROR again\n");
+}
+
+#[test]
+fn records_are_sieved_whole() {
+    let k = Kelvin::new("records_are_sieved_whole");
+    let path = k.file("app.log", "");
+
+    let out = Kelvin::follow(
+        &[
+            "--records",
+            "java",
+            "-s",
+            "IOException",
+            path.to_str().unwrap(),
+        ],
+        false,
+        || {
+            Kelvin::append(
+                &path,
+                "10:00:00 INFO start\n\
+                 10:00:01 ERROR failed\n\
+                 java.lang.IllegalStateException: boom\n\
+                 \tat com.example.Foo(Foo.java:10)\n\
+                 Caused by: java.io.IOException: disk full\n\
+                 \t... 3 more\n\
+                 10:00:02 INFO done\n",
+            );
+        },
+    );
+    assert_eq!(
+        out,
+        "10:00:01 ERROR failed\n\
+         java.lang.IllegalStateException: boom\n\
+         \tat com.example.Foo(Foo.java:10)\n\
+         Caused by: java.io.IOException: disk full\n\
+         \t... 3 more\n"
+    );
+}
+
+#[test]
+fn backlog_counts_records() {
+    let k = Kelvin::new("backlog_counts_records");
+    let contents = "2024-01-01 10:00:00 one\n  detail\n2024-01-01 10:00:01 two\n  a\n  b\n";
+    let path = k.file("app.log", contents);
+
+    let out = Kelvin::run(&[
+        "--record-start",
+        "^\\d{4}-",
+        "-n",
+        "1",
+        path.to_str().unwrap(),
+    ]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "2024-01-01 10:00:01 two\n  a\n  b\n"
+    );
+
+    let input = k.file("input.log", contents);
+    let out = Kelvin::pipe(&["--records", "timestamp", "-n", "1"], &input);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "2024-01-01 10:00:01 two\n  a\n  b\n"
+    );
+
+    // A file that starts part way through a record has that part as a record of its own.
+    let contents = "\tat foo.Bar(Baz.java:1)\nINFO line 1\n";
+    let path = k.file("rotated.log", contents);
+    let out = Kelvin::run(&["--records", "java", "-n", "2", path.to_str().unwrap()]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), contents);
+    let out = Kelvin::pipe(&["--records", "java", "-n", "2"], &path);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), contents);
+}
+
+#[test]
+fn json_lines_are_filtered_by_field() {
+    let k = Kelvin::new("json_lines_are_filtered_by_field");
+    let path = k.file(
+        "app.log",
+        "{\"ts\":\"10:00:00\",\"level\":\"info\",\"msg\":\"started\",\"port\":8080}\n\
+         {\"ts\":\"10:00:01\",\"level\":\"error\",\"msg\":\"failed\",\"latency_ms\":812,\"http\":{\"status\":502}}\n\
+         plain text\n\
+         {\"ts\":\"10:00:02\",\"level\":\"error\",\"msg\":\"slow\",\"latency_ms\":120,\"user\":\"a b\"}\n",
+    );
+    let path = path.to_str().unwrap();
+
+    let out = Kelvin::run(&["--json", "-n", "10", path]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "10:00:00 info started port=8080\n\
+         10:00:01 error failed latency_ms=812 http={\"status\":502}\n\
+         plain text\n\
+         10:00:02 error slow latency_ms=120 user=\"a b\"\n"
+    );
+
+    let out = Kelvin::run(&[
+        "--json",
+        "--where",
+        "level=error",
+        "--where",
+        "latency_ms>500",
+        "--fields",
+        "msg,http.status",
+        path,
+    ]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "failed http.status=502\nplain text\n"
+    );
+
+    let out = Kelvin::run(&["--json", "--where", "msg~^s", "-n", "1", path]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "10:00:02 error slow latency_ms=120 user=\"a b\"\n"
+    );
+}
+
+#[test]
+fn logfmt_lines_are_filtered_by_field() {
+    let k = Kelvin::new("logfmt_lines_are_filtered_by_field");
+    let path = k.file(
+        "app.log",
+        "time=10:00:00 level=info msg=\"listening on :80\" port=80\n\
+         time=10:00:01 level=error msg=\"upstream \\\"db\\\" timed out\" dur=1200ms status=502\n\
+         prose with a=b in it\n\
+         level=warn msg=retrying attempt=3\n",
+    );
+    let path = path.to_str().unwrap();
+
+    let out = Kelvin::run(&["--logfmt", "-n", "10", path]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "10:00:00 info listening on :80 port=80\n\
+         10:00:01 error upstream \"db\" timed out dur=1200ms status=502\n\
+         prose with a=b in it\n\
+         warn retrying attempt=3\n"
+    );
+
+    let out = Kelvin::run(&[
+        "--logfmt",
+        "--where",
+        "status>=500",
+        "--fields",
+        "level,status",
+        path,
+    ]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "error status=502\nprose with a=b in it\n"
+    );
+}
+
+#[test]
+fn known_formats_are_columnised() {
+    let k = Kelvin::new("known_formats_are_columnised");
+    let access = k.file(
+        "access.log",
+        "203.0.113.9 - - [01/Jan/2024:10:00:00 +0000] \"GET / HTTP/1.1\" 200 612 \"-\" \"curl/8.0\"\n\
+         198.51.100.23 - alice [01/Jan/2024:10:00:01 +0000] \"POST /api/orders HTTP/1.1\" 502 157 \"-\" \"curl/8.0\"\n\
+         not an access log line\n",
+    );
+    let access = access.to_str().unwrap();
+
+    let out = Kelvin::run(&["--format", "nginx", "--fields", "host,status,path", access]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "203.0.113.9 200 /\n\
+         198.51.100.23 502 /api/orders\n\
+         not an access log line\n"
+    );
+
+    let out = Kelvin::run(&["--format", "clf", "--where", "status>=500", access]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "198.51.100.23 - alice 01/Jan/2024:10:00:01 +0000 POST /api/orders HTTP/1.1 502 157\n\
+         not an access log line\n"
+    );
+
+    let syslog = k.file(
+        "syslog",
+        "Jan  1 10:00:00 web1 sshd[812]: Accepted publickey for deploy\n\
+         Jan  1 10:00:01 web1 kernel: eth0: link up\n",
+    );
+    let out = Kelvin::run(&["--format", "syslog", syslog.to_str().unwrap()]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "Jan  1 10:00:00 web1 sshd 812 Accepted publickey for deploy\n\
+         Jan  1 10:00:01 web1 kernel -   eth0: link up\n"
+    );
+}
+
+#[test]
+fn json_options_are_cli_errors() {
+    let out = Kelvin::run(&["--json", "--where", "level"]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("FIELD=VALUE"));
+
+    let out = Kelvin::run(&["--where", "level=error"]);
+    assert_eq!(out.status.code(), Some(2));
+
+    let out = Kelvin::run(&["--json", "--logfmt"]);
+    assert_eq!(out.status.code(), Some(2));
+}
+
+#[test]
+fn lines_are_filtered_and_coloured_by_level() {
+    let k = Kelvin::new("lines_are_filtered_and_coloured_by_level");
+    let path = k.file(
+        "app.log",
+        "10:00:00 DEBUG cache warm\n\
+         10:00:01 INFO started\n\
+         10:00:02 WARN disk full\n\
+         [error] request failed\n\
+         \tat Foo.bar(Foo.java:1)\n",
+    );
+    let path = path.to_str().unwrap();
+
+    let out = Kelvin::run(&["--min-level", "warn", path]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "10:00:02 WARN disk full\n[error] request failed\n\tat Foo.bar(Foo.java:1)\n"
+    );
+
+    let out = Kelvin::command(&["-n", "4", path])
+        .env("CLICOLOR_FORCE", "1")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "10:00:01 INFO started\n\
+         \x1b[33m10:00:02 WARN disk full\x1b[0m\n\
+         \x1b[31m[error] request failed\x1b[0m\n\
+         \tat Foo.bar(Foo.java:1)\n"
+    );
+
+    let json = k.file(
+        "app.json",
+        "{\"level\":\"debug\",\"msg\":\"tick\"}\n{\"level\":50,\"msg\":\"boom\"}\n",
+    );
+    let out = Kelvin::run(&["--json", "--min-level", "info", json.to_str().unwrap()]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "50 boom\n");
+
+    // JSON level fields are read without --json too.
+    let out = Kelvin::command(&["--min-level", "info", json.to_str().unwrap()])
+        .env("CLICOLOR_FORCE", "1")
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "\x1b[31m{\"level\":50,\"msg\":\"boom\"}\x1b[0m\n"
+    );
+}
+
+#[test]
+fn since_and_until_pick_a_time_span() {
+    let k = Kelvin::new("since_and_until_pick_a_time_span");
+    // A line a second for three hours, with a trace under every hundredth.
+    let mut contents = String::new();
+    for i in 0..3 * 3600 {
+        contents += &format!(
+            "2026-10-18T{:02}:{:02}:{:02}Z INFO line {}\n",
+            i / 3600,
+            i / 60 % 60,
+            i % 60,
+            i
+        );
+        if i % 100 == 0 {
+            contents += "\tat Foo.bar(Foo.java:1)\n";
+        }
+    }
+    let path = k.file("app.log", &contents);
+
+    let out = Kelvin::run(&[
+        "--since",
+        "2026-10-18T01:40:00Z",
+        "--until",
+        "2026-10-18T01:40:02Z",
+        path.to_str().unwrap(),
+    ]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "2026-10-18T01:40:00Z INFO line 6000\n\
+         \tat Foo.bar(Foo.java:1)\n\
+         2026-10-18T01:40:01Z INFO line 6001\n\
+         2026-10-18T01:40:02Z INFO line 6002\n"
+    );
+
+    let out = Kelvin::run(&["--since", "2026-10-18T02:59:59Z", path.to_str().unwrap()]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "2026-10-18T02:59:59Z INFO line 10799\n"
+    );
+
+    let input = k.file(
+        "input.log",
+        "18.10.2026 10:00:00 a\n18.10.2026 11:00:00 b\n18.10.2026 12:00:00 c\n",
+    );
+    let out = Kelvin::pipe(
+        &[
+            "--ts-format",
+            "%d.%m.%Y %H:%M:%S",
+            "--since",
+            "2026-10-18 10:30",
+            "--until",
+            "2026-10-18 11:30",
+        ],
+        &input,
+    );
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "18.10.2026 11:00:00 b\n"
+    );
+}
+
+#[test]
+fn invalid_times_are_cli_errors() {
+    let cases: [(&[&str], &str); 4] = [
+        (&["--since", "soon"], "expected a time"),
+        (&["--since", "5 parsecs ago"], "unknown unit 'parsecs'"),
+        (&["--ts-format", "%Q"], "not a valid strftime format"),
+        (&["--until", "now", "-n", "5"], "cannot be used with"),
+    ];
+    for (args, message) in cases {
+        let out = Kelvin::run(args);
+        assert_eq!(out.status.code(), Some(2), "{:?}", args);
+        assert!(
+            String::from_utf8(out.stderr).unwrap().contains(message),
+            "{:?}",
+            args
+        );
+    }
+}
+
+// Gzip `contents`, as logrotate's compress does.
+fn gzip(contents: &str) -> Vec<u8> {
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+    gz.write_all(contents.as_bytes()).unwrap();
+    gz.finish().unwrap()
+}
+
+#[test]
+fn compressed_files_are_read() {
+    let k = Kelvin::new("compressed_files_are_read");
+    let path = k.file("app.log.gz", gzip("one\ntwo\nthree\n"));
+
+    let out = Kelvin::run(&["-n", "2", path.to_str().unwrap()]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "two\nthree\n");
+
+    // Only its backlog is read when following it.
+    let file = path.to_str().unwrap();
+    let out = Kelvin::follow(&["-n", "+2", "-s", "t", file], false, || {});
+    assert_eq!(out, "two\nthree\n");
+}
+
+#[test]
+fn rotated_generations_are_stitched() {
+    let k = Kelvin::new("rotated_generations_are_stitched");
+    k.file(
+        "app.log.2.gz",
+        gzip("2026-10-18T10:00:00Z a\n2026-10-18T10:00:01Z b\n"),
+    );
+    k.file(
+        "app.log.1",
+        "2026-10-18T10:00:02Z c\n2026-10-18T10:00:03Z d\n",
+    );
+    k.file("app.log.bak", "2026-10-18T10:00:04Z ignored\n");
+    let path = k.file("app.log", "2026-10-18T10:00:05Z e\n");
+
+    let out = Kelvin::run(&["--include-rotated", "-n", "4", path.to_str().unwrap()]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "2026-10-18T10:00:01Z b\n\
+         2026-10-18T10:00:02Z c\n\
+         2026-10-18T10:00:03Z d\n\
+         2026-10-18T10:00:05Z e\n"
+    );
+
+    // Without the flag, only the current file is read.
+    let out = Kelvin::run(&["-n", "4", path.to_str().unwrap()]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "2026-10-18T10:00:05Z e\n"
+    );
+
+    let out = Kelvin::run(&[
+        "--include-rotated",
+        "--since",
+        "2026-10-18T10:00:01Z",
+        "--until",
+        "2026-10-18T10:00:02Z",
+        path.to_str().unwrap(),
+    ]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "2026-10-18T10:00:01Z b\n2026-10-18T10:00:02Z c\n"
+    );
+}
+
+#[test]
+fn rotated_generations_are_counted_from_the_oldest() {
+    let k = Kelvin::new("rotated_generations_are_counted_from_the_oldest");
+    k.file("app.log.2.gz", gzip("a\nb\n"));
+    k.file("app.log.1", "c\nd\n");
+    let path = k.file("app.log", "e\n");
+    let file = path.to_str().unwrap();
+
+    let out = Kelvin::run(&["--include-rotated", "-n", "+2", file]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "b\nc\nd\ne\n");
+
+    let out = Kelvin::run(&["--include-rotated", "-n", "+5", file]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "e\n");
+
+    let out = Kelvin::run(&["--include-rotated", "-c", "+6", file]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "\nd\ne\n");
+
+    let out = Kelvin::run(&["--include-rotated", "-c", "5", file]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "\nd\ne\n");
+}
+
+#[test]
+fn merge_interleaves_by_timestamp() {
+    let k = Kelvin::new("merge_interleaves_by_timestamp");
+    let api = k.file(
+        "api.log",
+        "2026-10-18T10:00:00Z start\n2026-10-18T10:00:02Z request\n  detail\n",
+    );
+    let db = k.file(
+        "db.log",
+        "2026-10-18T10:00:01Z connect\n2026-10-18T10:00:03Z query\n",
+    );
+    let (api, db) = (api.to_str().unwrap(), db.to_str().unwrap());
+
+    let out = Kelvin::run(&["--merge", "-q", "-n", "10", api, db]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "2026-10-18T10:00:00Z start\n\
+         2026-10-18T10:00:01Z connect\n\
+         2026-10-18T10:00:02Z request\n  \
+         detail\n\
+         2026-10-18T10:00:03Z query\n"
+    );
+
+    // Lines read within the window of each other are put in order.
+    let out = Kelvin::follow(&["--merge", "-f", "-n", "0", api, db], false, || {
+        Kelvin::append(&PathBuf::from(api), "2026-10-18T10:00:05Z late\n");
+        thread::sleep(Duration::from_millis(100));
+        Kelvin::append(&PathBuf::from(db), "2026-10-18T10:00:04Z early\n");
+    });
+    assert_eq!(
+        out,
+        format!(
+            "[{}] 2026-10-18T10:00:04Z early\n[{}] 2026-10-18T10:00:05Z late\n",
+            db, api
+        )
+    );
+}
+
+#[test]
+fn interactive_needs_a_terminal() {
+    let k = Kelvin::new("interactive_needs_a_terminal");
+    let path = k.file("app.log", "one\n");
+
+    let out = Kelvin::run(&["--interactive", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("--interactive needs a terminal"));
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn tui_needs_a_terminal() {
+    let k = Kelvin::new("tui_needs_a_terminal");
+    let path = k.file("app.log", "one\n");
+
+    let out = Kelvin::run(&["--tui", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("--tui needs a terminal"));
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn tui_takes_neither_records_nor_context() {
+    let k = Kelvin::new("tui_takes_neither_records_nor_context");
+    let path = k.file("app.log", "one\n");
+    let file = path.to_str().unwrap();
+
+    for flags in [
+        ["--records", "java"],
+        ["--record-start", "^x"],
+        ["-C", "2"],
+        ["-A", "1"],
+    ] {
+        let out = Kelvin::run(&["--tui", flags[0], flags[1], file]);
+        assert_eq!(out.status.code(), Some(2));
+        assert!(String::from_utf8(out.stderr)
+            .unwrap()
+            .contains("cannot be used with"));
+    }
+}
+
+#[test]
+fn multiple_files_get_headers() {
+    let k = Kelvin::new("multiple_files_get_headers");
+    k.file("a.log", "a1\na2\n");
+    k.file("b.log", "b1\n");
+
+    let glob = format!("{}/*.log", k.base);
+    let out = Kelvin::run(&["-n", "1", &glob]);
+    let expected = format!("==> {0}/a.log <==\na2\n\n==> {0}/b.log <==\nb1\n", k.base);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), expected);
+}
+
+#[test]
+fn follow_multiple_files_with_prefix() {
+    let k = Kelvin::new("follow_multiple_files_with_prefix");
+    let a = k.file("a.log", "");
+    let b = k.file("b.log", "");
+
+    let args = ["--prefix", "-f", a.to_str().unwrap(), b.to_str().unwrap()];
+    let out = Kelvin::follow(&args, false, || {
+        Kelvin::append(&b, "from b\n");
+        thread::sleep(SETTLE);
+        Kelvin::append(&a, "from a\n");
+    });
+    let expected = format!("[{}] from b\n[{}] from a\n", b.display(), a.display());
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn follow_name_survives_rotation() {
+    let k = Kelvin::new("follow_name_survives_rotation");
+    let path = k.file("app.log", "old\n");
+    let rotated = PathBuf::from(&k.base).join("app.log.1");
+
+    let out = Kelvin::follow(&["-F", "-n", "1", path.to_str().unwrap()], false, || {
+        Kelvin::append(&path, "before\n");
+        thread::sleep(SETTLE);
+        fs::rename(&path, &rotated).unwrap();
+        Kelvin::append(&rotated, "drained\n");
+        thread::sleep(SETTLE);
+        fs::write(&path, "after\n").unwrap();
+    });
+    assert_eq!(out, "old\nbefore\ndrained\nafter\n");
+}
+
+#[test]
+fn retry_waits_for_missing_file() {
+    let k = Kelvin::new("retry_waits_for_missing_file");
+    let path = PathBuf::from(&k.base).join("late.log");
+
+    let out = Kelvin::follow(&["-F", path.to_str().unwrap()], false, || {
+        fs::write(&path, "hello\n").unwrap();
+    });
+    assert_eq!(out, "hello\n");
+}
+
+#[test]
+fn retry_watches_for_missing_file_without_polling() {
+    let k = Kelvin::new("retry_watches_for_missing_file_without_polling");
+    let present = k.file("present.log", "");
+    let missing = PathBuf::from(&k.base).join("missing.log");
+
+    let child = Kelvin::command(&[
+        "-f",
+        "--retry",
+        present.to_str().unwrap(),
+        missing.to_str().unwrap(),
+    ])
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .unwrap();
+    thread::sleep(SETTLE);
+    fs::write(&missing, "hello\n").unwrap();
+    Kelvin::append(&present, "there\n");
+    thread::sleep(SETTLE);
+
+    let mut child = child;
+    child.kill().unwrap();
+    let out = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("hello\n"));
+    assert!(stdout.contains("there\n"));
+    assert!(!String::from_utf8(out.stderr).unwrap().contains("; polling"));
+}
+
+#[cfg(unix)]
+#[test]
+fn sigterm_stops_with_summary() {
+    let k = Kelvin::new("sigterm_stops_with_summary");
+    let path = k.file("app.log", "");
+
+    let child = Kelvin::command(&["-s", "hit", path.to_str().unwrap()])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(SETTLE);
+    Kelvin::append(&path, "hit\nmiss\n");
+    thread::sleep(SETTLE);
+
+    Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "hit\n");
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("2 lines read, 1 printed"));
+}
+
+#[test]
+fn truncation_banner_is_not_counted() {
+    let k = Kelvin::new("truncation_banner_is_not_counted");
+    let path = k.file("app.log", "old line\n");
+
+    let child = Kelvin::command(&["-f", "-n", "0", path.to_str().unwrap()])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(SETTLE);
+    fs::write(&path, "").unwrap();
+    thread::sleep(SETTLE);
+    Kelvin::append(&path, "new\n");
+    thread::sleep(SETTLE);
+
+    Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "***FILE TRUNCATED: READING FROM NEW EOF***\nnew\n"
+    );
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("1 lines read, 1 printed"));
+}
+
+#[test]
+fn poll_picks_up_appends() {
+    let k = Kelvin::new("poll_picks_up_appends");
+    let path = k.file("app.log", "");
+
+    let out = Kelvin::follow(&["--poll=0.1", path.to_str().unwrap()], false, || {
+        Kelvin::append(&path, "polled\n");
+    });
+    assert_eq!(out, "polled\n");
+}
+
+#[test]
+fn piped_stdin_is_followed_live() {
+    let mut child = Kelvin::command(&["-n", "2", "-s", "ERROR"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // The pipe is held open throughout, so anything printed was printed before EOF.
+    let mut input = child.stdin.take().unwrap();
+    input
+        .write_all(b"ERROR one\nERROR two\ninfo\nERROR three\n")
+        .unwrap();
+    thread::sleep(SETTLE);
+    input.write_all(b"warn\nERROR four\n").unwrap();
+    thread::sleep(SETTLE);
+
+    child.kill().unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "ERROR two\nERROR three\nERROR four\n"
+    );
+}
+
+#[test]
+fn followed_stdin_ends_at_eof() {
+    let mut child = Kelvin::command(&["-f", "-n", "1"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(b"a\nb\nc").unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "c\n");
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("3 lines read"));
+}
+
+#[test]
+fn partial_lines_are_sieved_whole() {
+    let k = Kelvin::new("partial_lines_are_sieved_whole");
+    let path = k.file("app.log", "INFO started\nERR");
+
+    let out = Kelvin::follow(&["-s", "ERROR", path.to_str().unwrap()], false, || {
+        Kelvin::append(&path, "OR half");
+        thread::sleep(SETTLE);
+        Kelvin::append(&path, " and half\n");
+    });
+    assert_eq!(out, "ERROR half and half\n");
+}
+
+#[test]
+fn partial_timeout_flushes_fragment() {
+    let k = Kelvin::new("partial_timeout_flushes_fragment");
+    let path = k.file("app.log", "");
+
+    let args = ["-f", "--partial-timeout", "0.1", path.to_str().unwrap()];
+    let out = Kelvin::follow(&args, false, || {
+        Kelvin::append(&path, "no newline yet");
+    });
+    assert_eq!(out, "no newline yet\n");
+}
+
+#[test]
+fn invalid_utf8_is_lossy_by_default() {
+    let k = Kelvin::new("invalid_utf8_is_lossy_by_default");
+    let path = k.file("app.log", b"caf\xe9 ERROR\nok\n");
+
+    let out = Kelvin::run(&["-n", "2", path.to_str().unwrap()]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "caf\u{fffd} ERROR\nok\n"
+    );
+}
+
+#[test]
+fn binary_escape_and_skip() {
+    let k = Kelvin::new("binary_escape_and_skip");
+    let path = k.file("app.log", b"bin\x00\xff\nok\n");
+    let file = path.to_str().unwrap();
+
+    let out = Kelvin::run(&["--binary=escape", file]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "bin\\x00\\xff\nok\n"
+    );
+
+    let out = Kelvin::run(&["--binary=skip", file]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "ok\n");
+}
+
+#[test]
+fn utf16le_and_latin1_are_decoded() {
+    let k = Kelvin::new("utf16le_and_latin1_are_decoded");
+
+    let mut utf16 = vec![0xff, 0xfe];
+    for unit in "first\r\nsecond \u{010a}\r\n".encode_utf16() {
+        utf16.extend_from_slice(&unit.to_le_bytes());
+    }
+    let path = k.file("service.log", utf16);
+    let out = Kelvin::run(&["--encoding=utf-16le", "-n", "1", path.to_str().unwrap()]);
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "second \u{010a}\n");
+
+    let path = k.file("latin1.log", b"d\xe9j\xe0 vu\n");
+    let out = Kelvin::run(&["--encoding=latin1", path.to_str().unwrap()]);
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "d\u{e9}j\u{e0} vu\n"
+    );
+}